@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use crate::path::astar_3d;
+use crate::{Coord, Elevation, PlaneDir, TileMap};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Agent {
+	pub pos: Coord,
+	pub elevation: Elevation,
+	// tiles still to walk, the next one first
+	pub path: Vec<(Elevation, Coord)>,
+	// how far along the step to path[0] we are, 0 to 1
+	pub progress: f32,
+	// set by an update that found the way ahead blocked and had to look for
+	// another, cleared by the next one
+	pub replanned: bool,
+}
+
+impl Agent {
+	pub fn new(elevation: Elevation, pos: Coord) -> Agent {
+		Agent{pos: pos, elevation: elevation, path: Vec::new(), progress: 0.0, replanned: false}
+	}
+
+	// takes a route as the pathfinders return it, starting where the agent stands
+	pub fn follow(&mut self, route: Vec<(Elevation, Coord)>) {
+		self.path = route;
+		if self.path.first() == Some(&(self.elevation, self.pos)) {
+			self.path.remove(0);
+		}
+		self.progress = 0.0;
+	}
+
+	pub fn next(&self) -> Option<&(Elevation, Coord)> {
+		self.path.first()
+	}
+
+	pub fn is_idle(&self) -> bool {
+		self.path.is_empty()
+	}
+
+	// whether the tile it's heading for can still be stepped onto, the map
+	// may have been edited since the route was found
+	pub fn next_step_open(&self, map: &TileMap) -> bool {
+		let Some((elevation, next)) = self.next() else {
+			return true;
+		};
+		let dx = next.x as isize - self.pos.x as isize;
+		let dy = next.y as isize - self.pos.y as isize;
+		let Some(dir) = PlaneDir::from_offset(dx, dy) else {
+			return false;
+		};
+		return map.can_enter(*elevation, next, dir.opposite());
+	}
+
+	// routes again from where it stands to the same goal, stopping if there's
+	// no way there any more
+	pub fn replan(&mut self, map: &TileMap) {
+		self.replanned = true;
+		let Some(goal) = self.path.last().copied() else {
+			return;
+		};
+		match astar_3d(map, (self.elevation, self.pos), goal) {
+			Some(route) => self.follow(route),
+			None => {
+				self.path.clear();
+				self.progress = 0.0;
+			},
+		}
+	}
+
+	// advance, but look for a new route first if the next tile got blocked
+	pub fn update(&mut self, map: &TileMap, dt: f32, speed: f32) {
+		self.replanned = false;
+		if !self.next_step_open(map) {
+			self.replan(map);
+		}
+		self.advance(dt, speed);
+	}
+
+	// speed is in tiles per second, the map isn't looked at
+	pub fn advance(&mut self, dt: f32, speed: f32) {
+		if self.path.is_empty() {
+			return;
+		}
+		self.progress += dt * speed;
+		while self.progress >= 1.0 && !self.path.is_empty() {
+			let (elevation, pos) = self.path.remove(0);
+			self.elevation = elevation;
+			self.pos = pos;
+			self.progress -= 1.0;
+		}
+		if self.path.is_empty() {
+			self.progress = 0.0;
+		}
+	}
+}
+
+// a new agent at start already on its way to goal, None without a route
+pub fn spawn(map: &TileMap, start: (Elevation, Coord), goal: (Elevation, Coord)) -> Option<Agent> {
+	let route = astar_3d(map, start, goal)?;
+	let mut agent = Agent::new(start.0, start.1);
+	agent.follow(route);
+	return Some(agent);
+}
+
+// which agents stand in each cell, a cell being a cell_size square of tiles
+// on one layer. agents go by their index in whatever list the caller keeps
+#[derive(Debug)]
+pub struct SpatialHash {
+	cell_size: usize,
+	cells: HashMap<(Elevation, Coord), Vec<usize>>,
+}
+
+impl SpatialHash {
+	pub fn new(cell_size: usize) -> SpatialHash {
+		assert!(cell_size > 0, "cells have to be at least one tile");
+		SpatialHash{cell_size: cell_size, cells: HashMap::new()}
+	}
+
+	// the cell a tile falls in
+	pub fn cell_of(&self, elevation: Elevation, pos: &Coord) -> (Elevation, Coord) {
+		(elevation, Coord{x: pos.x / self.cell_size, y: pos.y / self.cell_size})
+	}
+
+	pub fn insert(&mut self, id: usize, elevation: Elevation, pos: &Coord) {
+		let cell = self.cell_of(elevation, pos);
+		self.cells.entry(cell).or_default().push(id);
+	}
+
+	pub fn remove(&mut self, id: usize, elevation: Elevation, pos: &Coord) {
+		let cell = self.cell_of(elevation, pos);
+		if let Some(ids) = self.cells.get_mut(&cell) {
+			ids.retain(|i| *i != id);
+			if ids.is_empty() {
+				self.cells.remove(&cell);
+			}
+		}
+	}
+
+	// call when an agent steps from one tile to the next
+	pub fn relocate(&mut self, id: usize, from: (Elevation, Coord), to: (Elevation, Coord)) {
+		if self.cell_of(from.0, &from.1) != self.cell_of(to.0, &to.1) {
+			self.remove(id, from.0, &from.1);
+			self.insert(id, to.0, &to.1);
+		}
+	}
+
+	pub fn rebuild(&mut self, agents: &[Agent]) {
+		self.cells.clear();
+		for (id, a) in agents.iter().enumerate() {
+			self.insert(id, a.elevation, &a.pos);
+		}
+	}
+
+	// everyone in the cell holding pos
+	pub fn near(&self, elevation: Elevation, pos: &Coord) -> &[usize] {
+		self.cells.get(&self.cell_of(elevation, pos)).map_or(&[], |ids| ids.as_slice())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::path::test::grid_from_rows;
+	use crate::{Tile, TileDirTemplate};
+
+	#[test]
+	pub fn test_walk_path() {
+		let mut agent = Agent::new(Elevation(0), Coord{x: 0, y: 0});
+		let route = vec![(Elevation(0), Coord{x: 0, y: 0}), (Elevation(0), Coord{x: 1, y: 0}), (Elevation(1), Coord{x: 2, y: 0}), (Elevation(1), Coord{x: 2, y: 1})];
+		agent.follow(route);
+		assert_eq!(agent.path.len(), 3);
+
+		agent.advance(0.25, 2.0);
+		assert_eq!(agent.pos, Coord{x: 0, y: 0});
+		assert_eq!(agent.progress, 0.5);
+
+		agent.advance(0.25, 2.0);
+		assert_eq!(agent.pos, Coord{x: 1, y: 0});
+		assert_eq!(agent.next(), Some(&(Elevation(1), Coord{x: 2, y: 0})));
+
+		for _ in 0..10 {
+			agent.advance(0.25, 2.0);
+		}
+		assert_eq!((agent.elevation, agent.pos), (Elevation(1), Coord{x: 2, y: 1}));
+		assert!(agent.is_idle());
+		assert_eq!(agent.progress, 0.0);
+	}
+
+	#[test]
+	pub fn test_replan() {
+		let mut map = TileMap::new(3, 2);
+		map.layers.insert(Elevation(0), grid_from_rows(&[
+			"...",
+			"...",
+		]));
+		let e = Elevation(0);
+		let mut agent = spawn(&map, (e, Coord{x: 0, y: 0}), (e, Coord{x: 2, y: 0})).unwrap();
+		assert_eq!(agent.next(), Some(&(e, Coord{x: 1, y: 0})));
+		agent.update(&map, 0.1, 1.0);
+		assert!(!agent.replanned);
+
+		// a wall goes up right in front of it
+		map.set(e, &Coord{x: 1, y: 0}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		assert!(!agent.next_step_open(&map));
+		agent.update(&map, 0.1, 1.0);
+		assert!(agent.replanned);
+		assert_eq!(agent.pos, Coord{x: 0, y: 0});
+		assert_eq!(agent.path.first(), Some(&(e, Coord{x: 0, y: 1})));
+		assert_eq!(agent.path.last(), Some(&(e, Coord{x: 2, y: 0})));
+		assert!(!agent.path.contains(&(e, Coord{x: 1, y: 0})));
+		agent.update(&map, 0.1, 1.0);
+		assert!(!agent.replanned);
+
+		// and then the way round goes too, so it gives up
+		map.set(e, &Coord{x: 0, y: 1}, None);
+		agent.update(&map, 0.1, 1.0);
+		assert!(agent.replanned);
+		assert!(agent.is_idle());
+		assert_eq!(agent.pos, Coord{x: 0, y: 0});
+	}
+
+	#[test]
+	pub fn test_spatial_hash() {
+		let agents = vec![
+			Agent::new(Elevation(0), Coord{x: 0, y: 0}),
+			Agent::new(Elevation(0), Coord{x: 3, y: 3}),
+			Agent::new(Elevation(0), Coord{x: 4, y: 0}),
+			Agent::new(Elevation(1), Coord{x: 1, y: 1}),
+			Agent::new(Elevation(0), Coord{x: 3, y: 3}),
+		];
+		let mut hash = SpatialHash::new(4);
+		hash.rebuild(&agents);
+		assert_eq!(hash.near(Elevation(0), &Coord{x: 2, y: 1}), &[0, 1, 4]);
+		assert_eq!(hash.near(Elevation(0), &Coord{x: 7, y: 3}), &[2]);
+		assert_eq!(hash.near(Elevation(1), &Coord{x: 0, y: 0}), &[3]);
+		assert!(hash.near(Elevation(0), &Coord{x: 0, y: 4}).is_empty());
+
+		hash.relocate(1, (Elevation(0), Coord{x: 3, y: 3}), (Elevation(0), Coord{x: 4, y: 3}));
+		assert_eq!(hash.near(Elevation(0), &Coord{x: 0, y: 0}), &[0, 4]);
+		assert_eq!(hash.near(Elevation(0), &Coord{x: 4, y: 0}), &[2, 1]);
+		hash.remove(3, Elevation(1), &Coord{x: 1, y: 1});
+		assert!(hash.near(Elevation(1), &Coord{x: 1, y: 1}).is_empty());
+
+		// one tile per cell gives exactly who stands where
+		let mut tiles = SpatialHash::new(1);
+		tiles.rebuild(&agents);
+		assert_eq!(tiles.near(Elevation(0), &Coord{x: 3, y: 3}), &[1, 4]);
+		assert!(tiles.near(Elevation(0), &Coord{x: 2, y: 2}).is_empty());
+	}
+
+	#[test]
+	pub fn test_spawn() {
+		let mut map = TileMap::new(5, 2);
+		map.layers.insert(Elevation(0), grid_from_rows(&[
+			"..#..",
+			"..#..",
+		]));
+		let start = (Elevation(0), Coord{x: 0, y: 0});
+
+		let agent = spawn(&map, start, (Elevation(0), Coord{x: 1, y: 1})).unwrap();
+		assert_eq!((agent.elevation, agent.pos), start);
+		assert_eq!(agent.path, vec![(Elevation(0), Coord{x: 1, y: 0}), (Elevation(0), Coord{x: 1, y: 1})]);
+
+		assert_eq!(spawn(&map, start, (Elevation(0), Coord{x: 4, y: 0})), None);
+		assert_eq!(spawn(&map, start, (Elevation(1), Coord{x: 1, y: 0})), None);
+	}
+}
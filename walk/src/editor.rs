@@ -0,0 +1,765 @@
+use std::collections::HashSet;
+
+use crate::input::{Action, Button, InputEvent};
+use crate::{Coord, Elevation, ElevationDelta, Grid, PlaneDir, Tile, TileDirTemplate, TileMap};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// rectangle of tiles, both corners inclusive
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+	pub min: Coord,
+	pub max: Coord,
+}
+
+impl Region {
+	pub fn from_corners(a: &Coord, b: &Coord) -> Region {
+		Region{
+			min: Coord{x: a.x.min(b.x), y: a.y.min(b.y)},
+			max: Coord{x: a.x.max(b.x), y: a.y.max(b.y)},
+		}
+	}
+
+	pub fn coords(&self) -> impl Iterator<Item = Coord> + use<> {
+		let (min, max) = (self.min, self.max);
+		(min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Coord{x: x, y: y}))
+	}
+}
+
+// a fresh tile from make on every cell of the straight line from a to b,
+// skipping any of them off the grid
+pub fn tile_line(grid: &mut Grid<Tile>, a: Coord, b: Coord, make: impl Fn() -> Tile) {
+	for c in a.line_to(&b) {
+		if grid.contains(&c) {
+			grid.add(&c, make());
+		}
+	}
+}
+
+// a tile on every cell round the edge of the w x h rectangle, clipped to the grid
+pub fn tile_rect_outline(grid: &mut Grid<Tile>, top_left: Coord, w: usize, h: usize, make: impl Fn() -> Tile) {
+	stamp_rect(grid, top_left, w, h, |x, y| x == 0 || y == 0 || x == w - 1 || y == h - 1, make);
+}
+
+// a tile on every cell of the w x h rectangle, clipped to the grid
+pub fn tile_rect_filled(grid: &mut Grid<Tile>, top_left: Coord, w: usize, h: usize, make: impl Fn() -> Tile) {
+	stamp_rect(grid, top_left, w, h, |_, _| true, make);
+}
+
+// keep says which cells of the rectangle, relative to its top left, get one
+fn stamp_rect(grid: &mut Grid<Tile>, top_left: Coord, w: usize, h: usize, keep: impl Fn(usize, usize) -> bool, make: impl Fn() -> Tile) {
+	for y in 0..h {
+		for x in 0..w {
+			let (Some(gx), Some(gy)) = (top_left.x.checked_add(x), top_left.y.checked_add(y)) else {
+				continue;
+			};
+			let c = Coord{x: gx, y: gy};
+			if keep(x, y) && grid.contains(&c) {
+				grid.add(&c, make());
+			}
+		}
+	}
+}
+
+// tiles copied from a layer, placed relative to the top left of where they came from
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MapFragment {
+	pub tiles: Vec<(Coord, Tile)>,
+}
+
+impl MapFragment {
+	// empty cells in the region are left out, pasting won't clear anything
+	pub fn copy(map: &TileMap, elevation: Elevation, region: &Region) -> MapFragment {
+		let tiles = region.coords()
+			.filter_map(|c| {
+				let offset = Coord{x: c.x - region.min.x, y: c.y - region.min.y};
+				map.get(elevation, &c).map(|t| (offset, t.clone()))
+			})
+			.collect();
+		return MapFragment{tiles: tiles};
+	}
+
+	// the changes that put the fragment down with its top left at `at`,
+	// dropping whatever falls off the map. offsets loaded from a file can be
+	// anything, ones too big to add up are off the map too
+	pub fn stamp(&self, map: &TileMap, elevation: Elevation, at: &Coord) -> Vec<(Elevation, Coord, Option<Tile>)> {
+		let (width, height) = map.footprint();
+		self.tiles.iter()
+			.filter_map(|(offset, t)| Some((Coord{x: at.x.checked_add(offset.x)?, y: at.y.checked_add(offset.y)?}, t)))
+			.filter(|(c, _)| c.x < width && c.y < height)
+			.map(|(c, t)| (elevation, c, Some(t.clone())))
+			.collect()
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CellEdit {
+	elevation: Elevation,
+	coord: Coord,
+	before: Option<Tile>,
+	after: Option<Tile>,
+}
+
+// all edits in an action are undone and redone together
+#[derive(Clone, Debug, PartialEq)]
+struct EditAction {
+	edits: Vec<CellEdit>,
+}
+
+// collects the edits of one EditHistory::transaction while making them
+pub struct TxnRecorder<'a> {
+	map: &'a mut TileMap,
+	dirty: &'a mut HashSet<Coord>,
+	edits: Vec<CellEdit>,
+}
+
+impl TxnRecorder<'_> {
+	pub fn map(&self) -> &TileMap {
+		self.map
+	}
+
+	// returns what was there before, nothing is recorded if that's unchanged
+	pub fn set(&mut self, elevation: Elevation, coord: &Coord, after: Option<Tile>) -> Option<Tile> {
+		let before = self.map.set(elevation, coord, after.clone());
+		if before != after {
+			self.dirty.insert(*coord);
+			self.edits.push(CellEdit{elevation: elevation, coord: *coord, before: before.clone(), after: after});
+		}
+		return before;
+	}
+
+	pub fn apply(&mut self, changes: Vec<(Elevation, Coord, Option<Tile>)>) {
+		self.edits.reserve(changes.len());
+		for (elevation, coord, after) in changes {
+			self.set(elevation, &coord, after);
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+	done: Vec<EditAction>,
+	undone: Vec<EditAction>,
+	// cells changed by anything since the last clear_dirty, on any layer
+	dirty: HashSet<Coord>,
+}
+
+impl EditHistory {
+	pub fn new() -> EditHistory {
+		EditHistory::default()
+	}
+
+	// room for that many actions before either stack has to grow
+	pub fn with_capacity(actions: usize) -> EditHistory {
+		EditHistory{done: Vec::with_capacity(actions), undone: Vec::with_capacity(actions), dirty: HashSet::new()}
+	}
+
+	pub fn reserve(&mut self, actions: usize) {
+		self.done.reserve(actions);
+	}
+
+	// applies every change to the map as a single undoable action
+	pub fn apply(&mut self, map: &mut TileMap, changes: Vec<(Elevation, Coord, Option<Tile>)>) {
+		self.transaction(map, |txn| txn.apply(changes));
+	}
+
+	// everything f changes through the recorder is undone and redone as one
+	// action, however many separate edits it made
+	pub fn transaction(&mut self, map: &mut TileMap, f: impl FnOnce(&mut TxnRecorder)) {
+		let mut txn = TxnRecorder{map: map, dirty: &mut self.dirty, edits: Vec::new()};
+		f(&mut txn);
+		if txn.edits.is_empty() {
+			return;
+		}
+		self.done.push(EditAction{edits: txn.edits});
+		self.undone.clear();
+	}
+
+	pub fn undo(&mut self, map: &mut TileMap) -> bool {
+		let Some(action) = self.done.pop() else {
+			return false;
+		};
+		for e in action.edits.iter().rev() {
+			map.set(e.elevation, &e.coord, e.before.clone());
+			self.dirty.insert(e.coord);
+		}
+		self.undone.push(action);
+		return true;
+	}
+
+	pub fn redo(&mut self, map: &mut TileMap) -> bool {
+		let Some(action) = self.undone.pop() else {
+			return false;
+		};
+		for e in &action.edits {
+			map.set(e.elevation, &e.coord, e.after.clone());
+			self.dirty.insert(e.coord);
+		}
+		self.done.push(action);
+		return true;
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.done.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.undone.is_empty()
+	}
+
+	pub fn dirty(&self) -> &HashSet<Coord> {
+		&self.dirty
+	}
+
+	// once whatever shows the map has caught up
+	pub fn clear_dirty(&mut self) {
+		self.dirty.clear();
+	}
+}
+
+// a drag in progress, nothing touches the map or selection until it ends
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+	Idle,
+	// cells painted so far, placed as one action when the drag ends
+	Paint(Vec<Coord>),
+	Select{anchor: Coord, current: Coord},
+}
+
+// what's worth keeping from one run of the editor to the next, kept apart
+// from the map so opening another map doesn't lose it. the camera is whoever
+// draws the map's business, it's only carried here as plain numbers
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EditorState {
+	pub active_elevation: Elevation,
+	pub camera_target: (f32, f32),
+	pub camera_zoom: f32,
+	pub current_template: TileDirTemplate,
+	pub current_facing: PlaneDir,
+}
+
+impl Default for EditorState {
+	fn default() -> EditorState {
+		Editor::new().state((0.0, 0.0), 1.0)
+	}
+}
+
+#[derive(Debug)]
+pub struct Editor {
+	pub current_template: TileDirTemplate,
+	pub current_facing: PlaneDir,
+	pub selection: Option<Region>,
+	pub operation: Operation,
+	pub history: EditHistory,
+	// the layer that is drawn and edited
+	pub active_elevation: Elevation,
+	// where new agents start walking from
+	pub spawn_point: Option<(Elevation, Coord)>,
+	pub clipboard: Option<MapFragment>,
+	// the tile under the mouse as of the last Hover, where pastes go
+	pub hovered: Option<Coord>,
+}
+
+impl Default for Editor {
+	fn default() -> Editor {
+		Editor::new()
+	}
+}
+
+impl Editor {
+	pub fn new() -> Editor {
+		Editor{
+			current_template: TileDirTemplate::Floor,
+			current_facing: Tile::DEFAULT_FACING,
+			selection: None,
+			operation: Operation::Idle,
+			history: EditHistory::new(),
+			active_elevation: Elevation(0),
+			spawn_point: None,
+			clipboard: None,
+			hovered: None,
+		}
+	}
+
+	pub fn state(&self, camera_target: (f32, f32), camera_zoom: f32) -> EditorState {
+		EditorState{
+			active_elevation: self.active_elevation,
+			camera_target: camera_target,
+			camera_zoom: camera_zoom,
+			current_template: self.current_template,
+			current_facing: self.current_facing,
+		}
+	}
+
+	// picks up where state left off, the camera is left to the caller
+	pub fn restore(&mut self, state: &EditorState) {
+		self.active_elevation = state.active_elevation;
+		self.current_template = state.current_template;
+		self.current_facing = state.current_facing;
+	}
+
+	// moves the active layer up or down, creating it if the map doesn't have it yet
+	pub fn change_layer(&mut self, map: &mut TileMap, step: ElevationDelta) {
+		self.active_elevation += step;
+		map.layer_mut(self.active_elevation);
+	}
+
+	pub fn current_tile(&self) -> Tile {
+		Tile::from_template(self.current_template, self.current_facing)
+	}
+
+	pub fn place(&mut self, map: &mut TileMap, coord: &Coord) {
+		let change = (self.active_elevation, *coord, Some(self.current_tile()));
+		self.history.apply(map, vec![change]);
+	}
+
+	pub fn begin_paint(&mut self, coord: &Coord) {
+		self.operation = Operation::Paint(vec![*coord]);
+	}
+
+	pub fn begin_select(&mut self, coord: &Coord) {
+		self.operation = Operation::Select{anchor: *coord, current: *coord};
+	}
+
+	pub fn drag_to(&mut self, coord: &Coord) {
+		match &mut self.operation {
+			Operation::Idle => (),
+			Operation::Paint(cells) => {
+				if !cells.contains(coord) {
+					cells.push(*coord);
+				}
+			},
+			Operation::Select{current, ..} => *current = *coord,
+		}
+	}
+
+	pub fn end_drag(&mut self, map: &mut TileMap) {
+		match std::mem::replace(&mut self.operation, Operation::Idle) {
+			Operation::Idle => (),
+			Operation::Paint(cells) => {
+				let tile = self.current_tile();
+				let elevation = self.active_elevation;
+				let changes = cells.into_iter().map(|c| (elevation, c, Some(tile.clone()))).collect();
+				self.history.apply(map, changes);
+			},
+			Operation::Select{anchor, current} => {
+				self.selection = Some(Region::from_corners(&anchor, &current));
+			},
+		}
+	}
+
+	// does the editor's part of an event. saving, checking the map, the
+	// camera, agents and the clipboard file are left to the caller
+	pub fn handle(&mut self, map: &mut TileMap, event: &InputEvent) {
+		match *event {
+			InputEvent::Hover{coord} => self.hovered = coord,
+			InputEvent::Click{coord} => self.place(map, &coord),
+			// a quick right click drops the selection rather than selecting one tile
+			InputEvent::RightClick{..} => self.selection = None,
+			InputEvent::Pick{coord} => self.pick(map, &coord),
+			InputEvent::SetSpawn{coord} => self.spawn_point = Some((self.active_elevation, coord)),
+			InputEvent::Spawn{..} => (),
+			InputEvent::DragStart{button: Button::Left, coord} => self.begin_paint(&coord),
+			InputEvent::DragStart{button: Button::Right, coord} => self.begin_select(&coord),
+			InputEvent::DragMove{coord} => self.drag_to(&coord),
+			InputEvent::DragEnd => self.end_drag(map),
+			InputEvent::Key{action} => self.perform(map, action),
+			InputEvent::SelectTemplate{template} => self.current_template = template,
+		}
+	}
+
+	fn perform(&mut self, map: &mut TileMap, action: Action) {
+		match action {
+			Action::Cancel => self.cancel(),
+			Action::Rotate => self.current_facing = self.current_facing.clockwise(),
+			Action::NextLayer => self.change_layer(map, ElevationDelta(1)),
+			Action::PrevLayer => self.change_layer(map, ElevationDelta(-1)),
+			Action::FillSelection => self.fill_selection(map),
+			Action::ClearSelection => self.clear_selection(map),
+			Action::Undo => { self.history.undo(map); },
+			Action::Redo => { self.history.redo(map); },
+			Action::Copy | Action::CopyToFile => self.copy_selection(map),
+			Action::Paste | Action::PasteFromFile => {
+				if let Some(c) = self.hovered {
+					self.paste(map, &c);
+				}
+			},
+			Action::CheckMap | Action::Save | Action::FitView => (),
+		}
+	}
+
+	// drops the drag in progress without applying it
+	pub fn cancel(&mut self) {
+		self.operation = Operation::Idle;
+	}
+
+	// the selection to show, including one still being dragged out
+	pub fn shown_selection(&self) -> Option<Region> {
+		match &self.operation {
+			Operation::Select{anchor, current} => Some(Region::from_corners(anchor, current)),
+			_ => self.selection,
+		}
+	}
+
+	// eyedropper, takes the template and facing from an existing tile
+	pub fn pick(&mut self, map: &TileMap, coord: &Coord) {
+		let Some(tile) = map.get(self.active_elevation, coord) else {
+			return;
+		};
+		self.current_template = TileDirTemplate::infer(tile).unwrap_or(TileDirTemplate::Floor);
+		self.current_facing = tile.facing;
+	}
+
+	pub fn fill_selection(&mut self, map: &mut TileMap) {
+		let Some(region) = self.selection else {
+			return;
+		};
+		let tile = self.current_tile();
+		let elevation = self.active_elevation;
+		let changes = region.coords().map(|c| (elevation, c, Some(tile.clone()))).collect();
+		self.history.apply(map, changes);
+	}
+
+	pub fn clear_selection(&mut self, map: &mut TileMap) {
+		let Some(region) = self.selection else {
+			return;
+		};
+		let elevation = self.active_elevation;
+		let changes = region.coords().map(|c| (elevation, c, None)).collect();
+		self.history.apply(map, changes);
+	}
+
+	pub fn copy_selection(&mut self, map: &TileMap) {
+		let Some(region) = self.selection else {
+			return;
+		};
+		self.clipboard = Some(MapFragment::copy(map, self.active_elevation, &region));
+	}
+
+	pub fn paste(&mut self, map: &mut TileMap, at: &Coord) {
+		let Some(fragment) = &self.clipboard else {
+			return;
+		};
+		let changes = fragment.stamp(map, self.active_elevation, at);
+		self.history.apply(map, changes);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{DirInfo, TileType};
+
+	fn placed(grid: &Grid<Tile>) -> HashSet<Coord> {
+		grid.iter().map(|(c, _)| c).collect()
+	}
+
+	#[test]
+	pub fn test_tile_line() {
+		let road = || Tile::from_type(TileType::Road, PlaneDir::North);
+
+		let mut g = Grid::new(6, 5);
+		tile_line(&mut g, Coord{x: 4, y: 1}, Coord{x: 1, y: 1}, road);
+		assert_eq!(placed(&g), (1..=4).map(|x| Coord{x: x, y: 1}).collect());
+		assert!(g.iter().all(|(_, t)| t.ty == TileType::Road));
+
+		let mut g = Grid::new(6, 5);
+		tile_line(&mut g, Coord{x: 2, y: 0}, Coord{x: 2, y: 3}, road);
+		assert_eq!(placed(&g), (0..=3).map(|y| Coord{x: 2, y: y}).collect());
+
+		let mut g = Grid::new(6, 5);
+		tile_line(&mut g, Coord{x: 0, y: 0}, Coord{x: 3, y: 3}, road);
+		assert_eq!(placed(&g), (0..=3).map(|i| Coord{x: i, y: i}).collect());
+
+		// running off the bottom right only places what's on the grid
+		let mut g = Grid::new(6, 5);
+		tile_line(&mut g, Coord{x: 3, y: 2}, Coord{x: 7, y: 6}, road);
+		assert_eq!(placed(&g), HashSet::from([Coord{x: 3, y: 2}, Coord{x: 4, y: 3}, Coord{x: 5, y: 4}]));
+	}
+
+	#[test]
+	pub fn test_tile_rect() {
+		let wall = || Tile::from_type(TileType::Impasse, PlaneDir::North);
+		for (w, h) in [(4, 3), (5, 5), (2, 2), (6, 1), (1, 4)] {
+			let mut g = Grid::new(8, 8);
+			tile_rect_outline(&mut g, Coord{x: 1, y: 2}, w, h, wall);
+			let expected = if w == 1 || h == 1 { w * h } else { 2 * (w + h) - 4 };
+			assert_eq!(g.count_occupied(), expected, "{}x{}", w, h);
+			let mut g = Grid::new(8, 8);
+			tile_rect_filled(&mut g, Coord{x: 1, y: 2}, w, h, wall);
+			assert_eq!(g.count_occupied(), w * h, "{}x{}", w, h);
+		}
+
+		let mut g = Grid::new(8, 8);
+		tile_rect_outline(&mut g, Coord{x: 2, y: 2}, 4, 3, wall);
+		assert!(g.occupied(&Coord{x: 2, y: 2}) && g.occupied(&Coord{x: 5, y: 4}));
+		assert!(!g.occupied(&Coord{x: 3, y: 3}) && !g.occupied(&Coord{x: 4, y: 3}));
+
+		// half off the grid, only what's on it is placed
+		let mut g = Grid::new(8, 8);
+		tile_rect_filled(&mut g, Coord{x: 6, y: 5}, 4, 4, wall);
+		assert_eq!(g.count_occupied(), 2 * 3);
+		let mut g = Grid::new(8, 8);
+		tile_rect_outline(&mut g, Coord{x: 6, y: 5}, 4, 4, wall);
+		// the left and top sides are all that's left, sharing a corner
+		assert_eq!(g.count_occupied(), 2 + 3 - 1);
+		tile_rect_filled(&mut g, Coord{x: 0, y: 0}, 0, 3, wall);
+		assert_eq!(g.count_occupied(), 4);
+	}
+
+	fn snapshot(map: &TileMap, region: &Region) -> Vec<Option<Tile>> {
+		region.coords().map(|c| map.get(Elevation(0), &c).cloned()).collect()
+	}
+
+	#[test]
+	pub fn test_region_coords() {
+		let r = Region::from_corners(&Coord{x: 3, y: 1}, &Coord{x: 1, y: 2});
+		assert_eq!(r.min, Coord{x: 1, y: 1});
+		assert_eq!(r.max, Coord{x: 3, y: 2});
+		assert_eq!(r.coords().count(), 6);
+	}
+
+	#[test]
+	pub fn test_handle_events() {
+		let mut map = TileMap::new(4, 4);
+		let mut editor = Editor::new();
+		let e = editor.active_elevation;
+		let feed = |editor: &mut Editor, map: &mut TileMap, events: &[InputEvent]| {
+			for event in events {
+				editor.handle(map, event);
+			}
+		};
+
+		// a click places one tile
+		feed(&mut editor, &mut map, &[InputEvent::Hover{coord: Some(Coord{x: 1, y: 1})}, InputEvent::Click{coord: Coord{x: 1, y: 1}}]);
+		assert_eq!(placed(map.layer(e).unwrap()), HashSet::from([Coord{x: 1, y: 1}]));
+		assert_eq!(editor.operation, Operation::Idle);
+
+		// a left drag paints every tile it crosses, turned as the editor was
+		feed(&mut editor, &mut map, &[
+			InputEvent::Key{action: Action::Rotate},
+			InputEvent::SelectTemplate{template: TileDirTemplate::SingleWall},
+			InputEvent::DragStart{button: Button::Left, coord: Coord{x: 0, y: 3}},
+			InputEvent::DragMove{coord: Coord{x: 1, y: 3}},
+			InputEvent::DragMove{coord: Coord{x: 2, y: 3}},
+		]);
+		assert_eq!(map.layer(e).unwrap().count_occupied(), 1);
+		feed(&mut editor, &mut map, &[InputEvent::DragEnd]);
+		assert_eq!(map.layer(e).unwrap().count_occupied(), 4);
+		let wall = map.get(e, &Coord{x: 2, y: 3}).unwrap();
+		assert_eq!((wall.facing(), wall.get(&PlaneDir::East)), (PlaneDir::East, DirInfo::WALL));
+
+		// a right drag selects, delete clears it, a right click drops the selection
+		feed(&mut editor, &mut map, &[
+			InputEvent::DragStart{button: Button::Right, coord: Coord{x: 0, y: 3}},
+			InputEvent::DragMove{coord: Coord{x: 1, y: 3}},
+			InputEvent::DragEnd,
+		]);
+		assert_eq!(editor.selection, Some(Region::from_corners(&Coord{x: 0, y: 3}, &Coord{x: 1, y: 3})));
+		feed(&mut editor, &mut map, &[InputEvent::Key{action: Action::ClearSelection}]);
+		assert_eq!(placed(map.layer(e).unwrap()), HashSet::from([Coord{x: 1, y: 1}, Coord{x: 2, y: 3}]));
+		feed(&mut editor, &mut map, &[InputEvent::RightClick{coord: Coord{x: 3, y: 3}}]);
+		assert_eq!(editor.selection, None);
+
+		// undo puts the cleared tiles back, and keys the editor doesn't own do nothing to it
+		feed(&mut editor, &mut map, &[InputEvent::Key{action: Action::Undo}, InputEvent::Key{action: Action::Save}]);
+		assert_eq!(map.layer(e).unwrap().count_occupied(), 4);
+
+		// layers change by key, and spawn points land on the new one
+		feed(&mut editor, &mut map, &[InputEvent::Key{action: Action::NextLayer}, InputEvent::SetSpawn{coord: Coord{x: 2, y: 2}}]);
+		assert_eq!(editor.spawn_point, Some((Elevation(1), Coord{x: 2, y: 2})));
+	}
+
+	#[test]
+	pub fn test_transaction() {
+		let mut map = TileMap::new(4, 4);
+		let mut history = EditHistory::new();
+		let e = Elevation(0);
+		let spots = [Coord{x: 0, y: 0}, Coord{x: 1, y: 2}, Coord{x: 3, y: 3}];
+		history.transaction(&mut map, |txn| {
+			for c in &spots {
+				assert_eq!(txn.set(e, c, Some(Tile::new())), None);
+			}
+			assert!(txn.map().get(e, &spots[0]).is_some());
+			// setting it to what it already is isn't an edit
+			txn.set(e, &spots[0], Some(Tile::new()));
+		});
+		assert_eq!(history.done.len(), 1);
+		assert_eq!(history.done[0].edits.len(), 3);
+		assert_eq!(map.layer(e).unwrap().count_occupied(), 3);
+		assert_eq!(history.dirty(), &spots.into_iter().collect::<HashSet<_>>());
+
+		assert!(history.undo(&mut map));
+		assert!(map.layer(e).unwrap().is_empty());
+		assert!(!history.can_undo());
+		assert!(history.redo(&mut map));
+		assert_eq!(map.layer(e).unwrap().count_occupied(), 3);
+
+		// one that changes nothing leaves no action behind, and redo alone
+		history.undo(&mut map);
+		history.transaction(&mut map, |_| {});
+		assert!(history.can_redo());
+	}
+
+	#[test]
+	pub fn test_dirty_cells() {
+		let mut map = TileMap::new(4, 4);
+		let mut editor = Editor::new();
+		editor.place(&mut map, &Coord{x: 1, y: 1});
+		editor.selection = Some(Region::from_corners(&Coord{x: 2, y: 0}, &Coord{x: 3, y: 0}));
+		editor.fill_selection(&mut map);
+		let expected = HashSet::from([Coord{x: 1, y: 1}, Coord{x: 2, y: 0}, Coord{x: 3, y: 0}]);
+		assert_eq!(editor.history.dirty(), &expected);
+
+		// drawn, then only what changes after that
+		editor.history.clear_dirty();
+		assert!(editor.history.dirty().is_empty());
+		editor.place(&mut map, &Coord{x: 1, y: 1});
+		assert!(editor.history.dirty().is_empty());
+		editor.history.undo(&mut map);
+		assert_eq!(editor.history.dirty(), &HashSet::from([Coord{x: 2, y: 0}, Coord{x: 3, y: 0}]));
+		editor.history.clear_dirty();
+		editor.history.redo(&mut map);
+		assert_eq!(editor.history.dirty().len(), 2);
+	}
+
+	#[test]
+	pub fn test_history_capacity() {
+		let mut history = EditHistory::with_capacity(32);
+		assert!(history.done.capacity() >= 32 && history.undone.capacity() >= 32);
+		history.reserve(100);
+		assert!(history.done.capacity() >= 100);
+		assert!(!history.can_undo());
+	}
+
+	#[test]
+	pub fn test_fill_undo_redo() {
+		let mut map = TileMap::new(5, 5);
+		let mut editor = Editor::new();
+		let wall = Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East);
+		map.set(Elevation(0), &Coord{x: 2, y: 2}, Some(wall));
+
+		let region = Region::from_corners(&Coord{x: 1, y: 1}, &Coord{x: 3, y: 3});
+		let before = snapshot(&map, &region);
+
+		editor.selection = Some(region);
+		editor.current_template = TileDirTemplate::Impasse;
+		editor.fill_selection(&mut map);
+		let filled = snapshot(&map, &region);
+		assert!(filled.iter().all(|t| *t == Some(editor.current_tile())));
+
+		assert!(editor.history.undo(&mut map));
+		assert_eq!(snapshot(&map, &region), before);
+		assert!(!editor.history.can_undo());
+
+		assert!(editor.history.redo(&mut map));
+		assert_eq!(snapshot(&map, &region), filled);
+	}
+
+	#[test]
+	pub fn test_clear_undo() {
+		let mut map = TileMap::new(4, 4);
+		let mut editor = Editor::new();
+		editor.place(&mut map, &Coord{x: 0, y: 0});
+		editor.place(&mut map, &Coord{x: 1, y: 1});
+
+		editor.selection = Some(Region::from_corners(&Coord{x: 0, y: 0}, &Coord{x: 1, y: 1}));
+		editor.clear_selection(&mut map);
+		assert!(map.get(Elevation(0), &Coord{x: 0, y: 0}).is_none());
+		assert!(map.get(Elevation(0), &Coord{x: 1, y: 1}).is_none());
+
+		// one undo brings back both tiles
+		assert!(editor.history.undo(&mut map));
+		assert!(map.get(Elevation(0), &Coord{x: 0, y: 0}).is_some());
+		assert!(map.get(Elevation(0), &Coord{x: 1, y: 1}).is_some());
+	}
+
+	#[test]
+	pub fn test_change_layer() {
+		let mut map = TileMap::new(4, 3);
+		let mut editor = Editor::new();
+		editor.place(&mut map, &Coord{x: 0, y: 0});
+		assert_eq!(map.elevation_range(), Some((Elevation(0), Elevation(0))));
+
+		// going above the top layer creates an empty one of the same size
+		editor.change_layer(&mut map, ElevationDelta(1));
+		assert_eq!(editor.active_elevation, Elevation(1));
+		assert_eq!(map.elevation_range(), Some((Elevation(0), Elevation(1))));
+		let upper = map.layer(Elevation(1)).expect("layer 1 should exist");
+		assert_eq!((upper.width, upper.height), map.footprint());
+		assert!(map.get(Elevation(1), &Coord{x: 0, y: 0}).is_none());
+
+		// edits land on the active layer only
+		editor.place(&mut map, &Coord{x: 2, y: 2});
+		assert!(map.get(Elevation(1), &Coord{x: 2, y: 2}).is_some());
+		assert!(map.get(Elevation(0), &Coord{x: 2, y: 2}).is_none());
+
+		editor.change_layer(&mut map, ElevationDelta(-1));
+		editor.change_layer(&mut map, ElevationDelta(-1));
+		assert_eq!(editor.active_elevation, Elevation(-1));
+		assert_eq!(map.elevation_range(), Some((Elevation(-1), Elevation(1))));
+	}
+
+	#[test]
+	pub fn test_pick() {
+		let mut map = TileMap::new(3, 3);
+		let mut editor = Editor::new();
+		map.set(Elevation(0), &Coord{x: 1, y: 1}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::West)));
+		map.set(Elevation(0), &Coord{x: 2, y: 1}, Some(Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::South)));
+
+		editor.pick(&map, &Coord{x: 1, y: 1});
+		assert_eq!(editor.current_template, TileDirTemplate::Impasse);
+		assert_eq!(editor.current_facing, PlaneDir::West);
+
+		editor.pick(&map, &Coord{x: 2, y: 1});
+		assert_eq!(editor.current_template, TileDirTemplate::SingleWall);
+		assert_eq!(editor.current_facing, PlaneDir::South);
+
+		// nothing to pick from an empty cell
+		editor.pick(&map, &Coord{x: 0, y: 0});
+		assert_eq!(editor.current_template, TileDirTemplate::SingleWall);
+
+		// edges that don't match any template fall back to floor
+		let mut odd = Tile::new();
+		odd.set(&PlaneDir::North, DirInfo{elevation_delta: ElevationDelta(-2), ..DirInfo::OPEN});
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(odd));
+		editor.pick(&map, &Coord{x: 0, y: 0});
+		assert_eq!(editor.current_template, TileDirTemplate::Floor);
+	}
+
+	#[test]
+	pub fn test_cancel_drag() {
+		let mut map = TileMap::new(4, 4);
+		let mut editor = Editor::new();
+
+		editor.begin_paint(&Coord{x: 0, y: 0});
+		editor.drag_to(&Coord{x: 1, y: 0});
+		editor.drag_to(&Coord{x: 2, y: 0});
+		editor.cancel();
+		editor.end_drag(&mut map);
+		assert!(map.layer(Elevation(0)).is_none());
+		assert!(!editor.history.can_undo());
+
+		let old = Region::from_corners(&Coord{x: 0, y: 0}, &Coord{x: 1, y: 1});
+		editor.selection = Some(old);
+		editor.begin_select(&Coord{x: 2, y: 2});
+		editor.drag_to(&Coord{x: 3, y: 3});
+		assert_ne!(editor.shown_selection(), Some(old));
+		editor.cancel();
+		editor.end_drag(&mut map);
+		assert_eq!(editor.selection, Some(old));
+		assert_eq!(editor.operation, Operation::Idle);
+
+		// an uncancelled paint lands as a single action
+		editor.begin_paint(&Coord{x: 0, y: 3});
+		editor.drag_to(&Coord{x: 1, y: 3});
+		editor.end_drag(&mut map);
+		assert!(map.get(Elevation(0), &Coord{x: 0, y: 3}).is_some());
+		assert!(map.get(Elevation(0), &Coord{x: 1, y: 3}).is_some());
+		assert!(editor.history.undo(&mut map));
+		assert!(!editor.history.can_undo());
+	}
+}
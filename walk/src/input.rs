@@ -0,0 +1,281 @@
+// what the editor is told happened, worked out from the mouse and keyboard
+// by whatever has a window. nothing here reads them itself, so the editor
+// can be driven from tests
+
+use crate::{Coord, TileDirTemplate};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// everything a key can be bound to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Action {
+	Cancel,
+	Rotate,
+	NextLayer,
+	PrevLayer,
+	FillSelection,
+	ClearSelection,
+	Undo,
+	Redo,
+	Copy,
+	// as Copy, then saved to the shared clipboard file
+	CopyToFile,
+	// pastes at the hovered tile
+	Paste,
+	// as Paste, with the clipboard loaded from the shared file first
+	PasteFromFile,
+	CheckMap,
+	Save,
+	FitView,
+}
+
+impl Action {
+	pub const ALL: [Action; 15] = [
+		Self::Cancel, Self::Rotate, Self::NextLayer, Self::PrevLayer, Self::FillSelection,
+		Self::ClearSelection, Self::Undo, Self::Redo, Self::Copy, Self::CopyToFile, Self::Paste,
+		Self::PasteFromFile, Self::CheckMap, Self::Save, Self::FitView,
+	];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Button {
+	Left,
+	Right,
+}
+
+// one frame's worth comes in order, Hover always first
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InputEvent {
+	// the tile under the mouse, None off the grid
+	Hover{coord: Option<Coord>},
+	Click{coord: Coord},
+	RightClick{coord: Coord},
+	// alt left click
+	Pick{coord: Coord},
+	// shift right click and shift left click
+	SetSpawn{coord: Coord},
+	Spawn{coord: Coord},
+	// coord is where the button went down, not where the drag was noticed
+	DragStart{button: Button, coord: Coord},
+	DragMove{coord: Coord},
+	DragEnd,
+	Key{action: Action},
+	SelectTemplate{template: TileDirTemplate},
+}
+
+// what a press of a mouse button turned out to be once it was let go
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gesture {
+	Click,
+	Drag,
+}
+
+// follows one press from the button going down to it coming up, positions
+// are in pixels
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GestureTracker {
+	pressed_at: Option<(f32, f32)>,
+	// frames the button has been held for
+	frames: u32,
+	// went further than CLICK_SLOP from where it was pressed at some point
+	wandered: bool,
+}
+
+impl GestureTracker {
+	// a hand on a mouse shakes this much during a click
+	pub const CLICK_SLOP: f32 = 4.0;
+	// held this long it's a drag even without moving, about a third of a
+	// second at 60 fps
+	pub const CLICK_FRAMES: u32 = 20;
+
+	pub fn press(&mut self, pos: (f32, f32)) {
+		*self = GestureTracker{pressed_at: Some(pos), frames: 0, wandered: false};
+	}
+
+	// once a frame while the button is held
+	pub fn moved_to(&mut self, pos: (f32, f32)) {
+		let Some(start) = self.pressed_at else {
+			return;
+		};
+		self.frames += 1;
+		let (dx, dy) = (pos.0 - start.0, pos.1 - start.1);
+		if dx * dx + dy * dy > Self::CLICK_SLOP * Self::CLICK_SLOP {
+			self.wandered = true;
+		}
+	}
+
+	// None if nothing was pressed
+	pub fn release(&mut self, pos: (f32, f32)) -> Option<Gesture> {
+		self.moved_to(pos);
+		let gesture = if self.is_drag() { Gesture::Drag } else { Gesture::Click };
+		return self.pressed_at.take().map(|_| gesture);
+	}
+
+	// what it would be if let go now
+	pub fn is_drag(&self) -> bool {
+		self.pressed_at.is_some() && (self.wandered || self.frames >= Self::CLICK_FRAMES)
+	}
+}
+
+// what the mouse did this frame, read off the window by the caller
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MouseFrame {
+	pub pos: (f32, f32),
+	// the tile under pos
+	pub hovered: Option<Coord>,
+	// the button that went down this frame
+	pub pressed: Option<Button>,
+	pub down: bool,
+	pub released: bool,
+	pub shift: bool,
+	pub alt: bool,
+}
+
+// a press that could still turn into a click or a drag
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Press {
+	button: Button,
+	coord: Coord,
+}
+
+// turns each frame's mouse into events, keeping track of presses from one
+// frame to the next
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputState {
+	gesture: GestureTracker,
+	press: Option<Press>,
+	dragging: bool,
+	// where the last DragMove went, so each tile is only sent once in a row
+	dragged_to: Option<Coord>,
+}
+
+impl InputState {
+	pub fn new() -> InputState {
+		InputState::default()
+	}
+
+	pub fn mouse(&mut self, m: &MouseFrame) -> Vec<InputEvent> {
+		let mut out = vec![InputEvent::Hover{coord: m.hovered}];
+		if let Some(button) = m.pressed {
+			self.press = None;
+			self.dragging = false;
+			if let Some(c) = m.hovered {
+				match (button, m.shift, m.alt) {
+					(Button::Right, true, _) => out.push(InputEvent::SetSpawn{coord: c}),
+					(Button::Left, true, _) => out.push(InputEvent::Spawn{coord: c}),
+					(Button::Left, false, true) => out.push(InputEvent::Pick{coord: c}),
+					_ => {
+						self.gesture.press(m.pos);
+						self.press = Some(Press{button: button, coord: c});
+					},
+				}
+			}
+		} else if m.down && let Some(p) = self.press {
+			self.gesture.moved_to(m.pos);
+			if self.gesture.is_drag() {
+				self.drag(p, m.hovered, &mut out);
+			}
+		}
+
+		if m.released && let Some(p) = self.press.take() {
+			if self.gesture.release(m.pos) == Some(Gesture::Drag) {
+				// starts it too, if the release came before it was noticed
+				self.drag(p, m.hovered, &mut out);
+				out.push(InputEvent::DragEnd);
+			} else {
+				out.push(match p.button {
+					Button::Left => InputEvent::Click{coord: p.coord},
+					Button::Right => InputEvent::RightClick{coord: p.coord},
+				});
+			}
+			self.dragging = false;
+			self.dragged_to = None;
+		}
+		return out;
+	}
+
+	fn drag(&mut self, p: Press, hovered: Option<Coord>, out: &mut Vec<InputEvent>) {
+		if !self.dragging {
+			self.dragging = true;
+			self.dragged_to = Some(p.coord);
+			out.push(InputEvent::DragStart{button: p.button, coord: p.coord});
+		}
+		if let Some(c) = hovered && self.dragged_to != Some(c) {
+			self.dragged_to = Some(c);
+			out.push(InputEvent::DragMove{coord: c});
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	pub fn test_gesture() {
+		let mut g = GestureTracker::default();
+		assert_eq!(g.release((0.0, 0.0)), None);
+
+		// a wobble within the slop is still a click
+		g.press((100.0, 100.0));
+		g.moved_to((101.0, 102.0));
+		g.moved_to((98.0, 99.0));
+		assert!(!g.is_drag());
+		assert_eq!(g.release((102.0, 101.0)), Some(Gesture::Click));
+		assert_eq!(g.release((102.0, 101.0)), None);
+
+		// wandering off and back counts as a drag
+		g.press((100.0, 100.0));
+		g.moved_to((110.0, 100.0));
+		g.moved_to((100.0, 100.0));
+		assert_eq!(g.release((100.0, 100.0)), Some(Gesture::Drag));
+
+		// so does being let go far away, even with no frames between
+		g.press((0.0, 0.0));
+		assert_eq!(g.release((0.0, 5.0)), Some(Gesture::Drag));
+
+		// and holding still for long enough
+		g.press((50.0, 50.0));
+		for _ in 0..GestureTracker::CLICK_FRAMES {
+			g.moved_to((50.0, 50.0));
+		}
+		assert!(g.is_drag());
+		assert_eq!(g.release((50.0, 50.0)), Some(Gesture::Drag));
+	}
+
+	#[test]
+	pub fn test_input_state() {
+		let mut input = InputState::new();
+		let at = |x: f32, c: Coord| MouseFrame{pos: (x, 10.0), hovered: Some(c), ..Default::default()};
+		let (a, b, c) = (Coord{x: 0, y: 0}, Coord{x: 1, y: 0}, Coord{x: 2, y: 0});
+
+		// down and straight back up is a click where it went down
+		let events = input.mouse(&MouseFrame{pressed: Some(Button::Left), down: true, ..at(5.0, a)});
+		assert_eq!(events, vec![InputEvent::Hover{coord: Some(a)}]);
+		let events = input.mouse(&MouseFrame{released: true, ..at(6.0, a)});
+		assert_eq!(events, vec![InputEvent::Hover{coord: Some(a)}, InputEvent::Click{coord: a}]);
+
+		// a right drag across three tiles
+		input.mouse(&MouseFrame{pressed: Some(Button::Right), down: true, ..at(5.0, a)});
+		let events = input.mouse(&MouseFrame{down: true, ..at(30.0, b)});
+		assert_eq!(&events[1..], &[InputEvent::DragStart{button: Button::Right, coord: a}, InputEvent::DragMove{coord: b}]);
+		// nothing new while it stays on the same tile
+		assert_eq!(input.mouse(&MouseFrame{down: true, ..at(35.0, b)}).len(), 1);
+		let events = input.mouse(&MouseFrame{released: true, ..at(60.0, c)});
+		assert_eq!(&events[1..], &[InputEvent::DragMove{coord: c}, InputEvent::DragEnd]);
+		assert_eq!(input.mouse(&MouseFrame{released: true, ..at(60.0, c)}).len(), 1);
+
+		// modifiers act on the press and leave nothing to release
+		let events = input.mouse(&MouseFrame{pressed: Some(Button::Left), alt: true, ..at(5.0, b)});
+		assert_eq!(events[1], InputEvent::Pick{coord: b});
+		assert_eq!(input.mouse(&MouseFrame{released: true, ..at(5.0, b)}).len(), 1);
+		let events = input.mouse(&MouseFrame{pressed: Some(Button::Right), shift: true, ..at(5.0, c)});
+		assert_eq!(events[1], InputEvent::SetSpawn{coord: c});
+
+		// off the grid a press is ignored
+		let events = input.mouse(&MouseFrame{pressed: Some(Button::Left), pos: (-5.0, -5.0), ..Default::default()});
+		assert_eq!(events, vec![InputEvent::Hover{coord: None}]);
+		assert_eq!(input.mouse(&MouseFrame{released: true, pos: (-5.0, -5.0), ..Default::default()}).len(), 1);
+	}
+}
@@ -0,0 +1,2339 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+// the map engine, everything but drawing and input so it can be used
+// without a window
+
+use std::collections::BTreeMap;
+use std::iter::repeat_with;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub mod agent;
+pub mod editor;
+pub mod input;
+pub mod lint;
+pub mod mapfile;
+pub mod mapgen;
+pub mod path;
+pub mod prelude;
+
+/// which layer a tile is on. only a delta can be added to one, and two of
+/// them only give the delta between them
+///
+/// ```
+/// use poubelle::{Elevation, ElevationDelta, TileMap};
+///
+/// assert_eq!(Elevation(1) + ElevationDelta(2), Elevation(3));
+/// assert_eq!(Elevation(1) - Elevation(3), ElevationDelta(-2));
+/// assert!(TileMap::new(2, 2).layer(Elevation(1)).is_none());
+/// ```
+///
+/// adding two elevations is a type error
+///
+/// ```compile_fail
+/// use poubelle::Elevation;
+///
+/// let _ = Elevation(1) + Elevation(2);
+/// ```
+///
+/// and so is a delta where an elevation is wanted
+///
+/// ```compile_fail
+/// use poubelle::{ElevationDelta, TileMap};
+///
+/// let _ = TileMap::new(2, 2).layer(ElevationDelta(1));
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Elevation(pub i32);
+
+// how far one elevation is above another, negative for below
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ElevationDelta(pub i32);
+
+impl Elevation {
+	// layers between self and other, whichever is higher
+	pub fn abs_diff(&self, other: Elevation) -> u32 {
+		self.0.abs_diff(other.0)
+	}
+}
+
+impl std::ops::Add<ElevationDelta> for Elevation {
+	type Output = Elevation;
+
+	fn add(self, delta: ElevationDelta) -> Elevation {
+		Elevation(self.0 + delta.0)
+	}
+}
+
+impl std::ops::AddAssign<ElevationDelta> for Elevation {
+	fn add_assign(&mut self, delta: ElevationDelta) {
+		self.0 += delta.0;
+	}
+}
+
+impl std::ops::Sub for Elevation {
+	type Output = ElevationDelta;
+
+	fn sub(self, other: Elevation) -> ElevationDelta {
+		ElevationDelta(self.0 - other.0)
+	}
+}
+
+impl std::ops::Add for ElevationDelta {
+	type Output = ElevationDelta;
+
+	fn add(self, other: ElevationDelta) -> ElevationDelta {
+		ElevationDelta(self.0 + other.0)
+	}
+}
+
+impl std::ops::Neg for ElevationDelta {
+	type Output = ElevationDelta;
+
+	fn neg(self) -> ElevationDelta {
+		ElevationDelta(-self.0)
+	}
+}
+
+impl std::fmt::Display for Elevation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Coord {
+	pub x: usize,
+	pub y: usize,
+}
+
+impl Coord {
+	// neighbour in the given direction, None off the top or left
+	pub fn step(&self, dir: PlaneDir) -> Option<Coord> {
+		let (dx, dy) = dir.offset();
+		Some(Coord{x: self.x.checked_add_signed(dx)?, y: self.y.checked_add_signed(dy)?})
+	}
+
+	pub fn manhattan_distance(&self, other: &Coord) -> usize {
+		self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+	}
+
+	// counting diagonal steps as one, how many rings out other is
+	pub fn chebyshev_distance(&self, other: &Coord) -> usize {
+		self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+	}
+
+	// cells on the bresenham line from self to other, both ends included
+	pub fn line_to(&self, other: &Coord) -> Vec<Coord> {
+		let (x0, y0) = (self.x as isize, self.y as isize);
+		let (x1, y1) = (other.x as isize, other.y as isize);
+		let dx = (x1 - x0).abs();
+		let dy = -(y1 - y0).abs();
+		let sx = if x0 < x1 { 1 } else { -1 };
+		let sy = if y0 < y1 { 1 } else { -1 };
+
+		let mut out = Vec::new();
+		let (mut x, mut y) = (x0, y0);
+		let mut err = dx + dy;
+		loop {
+			out.push(Coord{x: x as usize, y: y as usize});
+			if x == x1 && y == y1 {
+				return out;
+			}
+			let e2 = 2 * err;
+			if e2 >= dy {
+				err += dy;
+				x += sx;
+			}
+			if e2 <= dx {
+				err += dx;
+				y += sy;
+			}
+		}
+	}
+}
+
+// edges are saved as a map holding only the ones that were set, the same
+// as when tiles kept them in a HashMap
+#[cfg(feature = "serde")]
+mod edge_map {
+	use std::collections::BTreeMap;
+
+	use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+	use crate::{DirInfo, PlaneDir};
+
+	pub fn serialize<S: Serializer>(edges: &[Option<DirInfo>; 4], serializer: S) -> Result<S::Ok, S::Error> {
+		let set: BTreeMap<PlaneDir, DirInfo> = PlaneDir::ALL.into_iter()
+			.filter_map(|d| edges[d as usize].map(|info| (d, info)))
+			.collect();
+		return set.serialize(serializer);
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[Option<DirInfo>; 4], D::Error> {
+		let mut edges = [None; 4];
+		for (dir, info) in BTreeMap::<PlaneDir, DirInfo>::deserialize(deserializer)? {
+			edges[dir as usize] = Some(info);
+		}
+		return Ok(edges);
+	}
+}
+
+impl From<&(usize, usize)> for Coord {
+	fn from(input: &(usize, usize)) -> Self {
+		Coord{x: input.0, y: input.1}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "GridData<T>"))]
+pub struct Grid<T>{
+	tile_array: Box<[Option<T>]>,
+	width: usize,
+	height: usize,
+	// bit i is set exactly when tile_array[i] is Some
+	#[cfg_attr(feature = "serde", serde(skip))]
+	occupied: Occupancy,
+}
+
+// a coord past the edge of a grid
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutOfBounds {
+	coord: Coord,
+	width: usize,
+	height: usize,
+}
+
+impl std::fmt::Display for OutOfBounds {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "coord {:?} width {} height {}", self.coord, self.width, self.height)
+	}
+}
+
+impl std::error::Error for OutOfBounds {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GridAllocError {
+	// width * height doesn't fit in a usize
+	TooLarge{width: usize, height: usize},
+}
+
+impl std::fmt::Display for GridAllocError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TooLarge{width, height} => write!(f, "a {}x{} grid has too many cells", width, height),
+		}
+	}
+}
+
+impl std::error::Error for GridAllocError {}
+
+// one bit per cell, set for the ones holding something
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Occupancy {
+	words: Vec<u64>,
+}
+
+impl Occupancy {
+	pub fn new(len: usize) -> Occupancy {
+		Occupancy{words: vec![0; len.div_ceil(64)]}
+	}
+
+	pub fn set(&mut self, i: usize, occupied: bool) {
+		if occupied {
+			self.words[i / 64] |= 1 << (i % 64);
+		} else {
+			self.words[i / 64] &= !(1 << (i % 64));
+		}
+	}
+
+	pub fn contains(&self, i: usize) -> bool {
+		self.words[i / 64] & (1 << (i % 64)) != 0
+	}
+
+	pub fn count(&self) -> usize {
+		self.words.iter().map(|w| w.count_ones() as usize).sum()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.words.iter().all(|w| *w == 0)
+	}
+
+	pub fn first(&self) -> Option<usize> {
+		let (word, bits) = self.words.iter().enumerate().find(|(_, w)| **w != 0)?;
+		return Some(word * 64 + bits.trailing_zeros() as usize);
+	}
+
+	pub fn clear(&mut self) {
+		self.words.iter_mut().for_each(|w| *w = 0);
+	}
+}
+
+// a grid as saved, the occupancy bits are rebuilt on load
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct GridData<T> {
+	tile_array: Box<[Option<T>]>,
+	width: usize,
+	height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl<T> From<GridData<T>> for Grid<T> {
+	fn from(data: GridData<T>) -> Grid<T> {
+		let mut g = Grid{tile_array: data.tile_array, width: data.width, height: data.height, occupied: Occupancy::default()};
+		g.rebuild_occupied();
+		return g;
+	}
+}
+
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PlaneDir {
+	North = 0u8,
+	East = 1u8,
+	South = 2u8,
+	West = 3u8,
+}
+
+impl TryFrom<u8> for PlaneDir {
+	type Error = &'static str;
+
+	fn try_from(val: u8) -> Result<Self, Self::Error> {
+		match val {
+			0 => Ok(Self::North),
+			1 => Ok(Self::East),
+			2 => Ok(Self::South),
+			3 => Ok(Self::West),
+			_ => Err("bad value for PlaneDir")
+		}
+	}
+}
+
+impl PlaneDir {
+	pub const ALL: [PlaneDir; 4] = [PlaneDir::North, PlaneDir::East, PlaneDir::South, PlaneDir::West];
+
+	// gives x s.t. self.rotate(x) == other. x is just the difference of the
+	// two as numbers, anywhere in -3..=3, so North to West is -3 not 1
+	pub fn rotate_diff(&self, other: PlaneDir) -> i8 {
+		(other as i8) - (*self as i8)
+	}
+
+	// same as rotate_diff but the fewest quarter turns, always in -1..=2 with
+	// a half turn going clockwise
+	pub fn shortest_rotate_diff(&self, other: PlaneDir) -> i8 {
+		match self.rotate_diff(other).rem_euclid(4) {
+			3 => -1,
+			n => n,
+		}
+	}
+
+	// quarter turns clockwise, negative n turns the other way
+	pub fn rotate(&self, n: i8) -> PlaneDir {
+		// reduce n first so the sum can't overflow at the ends of i8
+		match (*self as u8 + n.rem_euclid(4) as u8) % 4 {
+			0 => PlaneDir::North,
+			1 => PlaneDir::East,
+			2 => PlaneDir::South,
+			_ => PlaneDir::West,
+		}
+	}
+
+	// step to the neighbouring tile, y grows southwards
+	pub fn offset(&self) -> (isize, isize) {
+		match self {
+			PlaneDir::North => (0, -1),
+			PlaneDir::East => (1, 0),
+			PlaneDir::South => (0, 1),
+			PlaneDir::West => (-1, 0),
+		}
+	}
+
+	// inverse of offset, None unless it's a single step along one axis
+	pub fn from_offset(dx: isize, dy: isize) -> Option<PlaneDir> {
+		PlaneDir::ALL.into_iter().find(|d| d.offset() == (dx, dy))
+	}
+
+	pub fn opposite(&self) -> PlaneDir {
+		self.rotate(2)
+	}
+
+	// as seen in a mirror standing north to south, East and West swap
+	pub fn flipped_horizontal(&self) -> PlaneDir {
+		match self {
+			PlaneDir::East | PlaneDir::West => self.opposite(),
+			_ => *self,
+		}
+	}
+
+	// North and South swap
+	pub fn flipped_vertical(&self) -> PlaneDir {
+		match self {
+			PlaneDir::North | PlaneDir::South => self.opposite(),
+			_ => *self,
+		}
+	}
+
+	pub fn clockwise(&self) -> PlaneDir {
+		self.rotate(1)
+	}
+
+	pub fn anticlockwise(&self) -> PlaneDir {
+		self.rotate(-1)
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DirInfo {
+	pub elevation_delta: ElevationDelta,
+	pub enterable: bool,
+	// what a weighted route pays to cross this edge, on top of the tile it
+	// steps onto. maps from before there was a cost leave it out
+	#[cfg_attr(feature = "serde", serde(default = "DirInfo::default_cost", skip_serializing_if = "DirInfo::is_default_cost"))]
+	pub cost: u32,
+}
+
+impl DirInfo {
+	pub const DEFAULT_COST: u32 = 1;
+	pub const OPEN: DirInfo = DirInfo{elevation_delta: ElevationDelta(0), enterable: true, cost: Self::DEFAULT_COST};
+	pub const WALL: DirInfo = DirInfo{enterable: false, ..Self::OPEN};
+
+	#[cfg(feature = "serde")]
+	fn default_cost() -> u32 {
+		Self::DEFAULT_COST
+	}
+
+	#[cfg(feature = "serde")]
+	fn is_default_cost(cost: &u32) -> bool {
+		*cost == Self::DEFAULT_COST
+	}
+
+	pub fn is_wall(&self) -> bool {
+		!self.enterable
+	}
+
+	// goes up or down a layer on the way through
+	pub fn is_step(&self) -> bool {
+		self.elevation_delta != ElevationDelta(0)
+	}
+
+	// plain floor, can be walked over without changing layer
+	pub fn is_open(&self) -> bool {
+		!self.is_wall() && !self.is_step()
+	}
+}
+
+impl Default for DirInfo {
+	fn default() -> DirInfo {
+		DirInfo::OPEN
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TileDirTemplate {
+	Floor,
+	SingleWall,
+	Impasse,
+	Stair,
+	Ramp,
+}
+
+impl TileDirTemplate {
+	pub const ALL: [TileDirTemplate; 5] = [
+		TileDirTemplate::Floor,
+		TileDirTemplate::SingleWall,
+		TileDirTemplate::Impasse,
+		TileDirTemplate::Stair,
+		TileDirTemplate::Ramp,
+	];
+
+	// edge info for a tile of this template facing north
+	fn dir_info(&self, dir: PlaneDir) -> DirInfo {
+		match (self, dir) {
+			(Self::Floor, _) => DirInfo::OPEN,
+			(Self::SingleWall, PlaneDir::North) => DirInfo::WALL,
+			(Self::SingleWall, _) => DirInfo::OPEN,
+			(Self::Impasse, _) => DirInfo::WALL,
+			// walk up through the facing edge, walled in at the sides
+			(Self::Stair | Self::Ramp, PlaneDir::North) => DirInfo{elevation_delta: ElevationDelta(1), ..DirInfo::OPEN},
+			(Self::Stair | Self::Ramp, PlaneDir::South) => DirInfo::OPEN,
+			(Self::Stair | Self::Ramp, _) => DirInfo::WALL,
+		}
+	}
+
+	// what a tile built from this template is, lacking anything better
+	pub fn default_type(&self) -> TileType {
+		match self {
+			Self::Floor | Self::SingleWall => TileType::Freewalk,
+			Self::Impasse => TileType::Impasse,
+			Self::Stair => TileType::Stair,
+			Self::Ramp => TileType::Ramp,
+		}
+	}
+
+	// the template that would build this tile at its current facing.
+	// ramps have the same edges as stairs so they read back as stairs
+	pub fn infer(tile: &Tile) -> Option<TileDirTemplate> {
+		Self::ALL.into_iter().find(|t| Tile::from_template(*t, tile.facing).same_edges(tile))
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TileType {
+	Sidewalk,
+	#[default]
+	Freewalk,
+	Impasse,
+	Stair,
+	Ramp,
+	Road,
+}
+
+impl TileType {
+	pub const ALL: [TileType; 6] = [Self::Sidewalk, Self::Freewalk, Self::Impasse, Self::Stair, Self::Ramp, Self::Road];
+
+	pub fn template(&self) -> TileDirTemplate {
+		match self {
+			Self::Sidewalk | Self::Freewalk | Self::Road => TileDirTemplate::Floor,
+			Self::Impasse => TileDirTemplate::Impasse,
+			Self::Stair => TileDirTemplate::Stair,
+			Self::Ramp => TileDirTemplate::Ramp,
+		}
+	}
+
+	// the character for this type in ascii maps
+	pub fn from_char(ch: char) -> Option<TileType> {
+		match ch {
+			's' => Some(Self::Sidewalk),
+			'.' => Some(Self::Freewalk),
+			'#' => Some(Self::Impasse),
+			'^' => Some(Self::Stair),
+			'/' => Some(Self::Ramp),
+			'r' => Some(Self::Road),
+			_ => None,
+		}
+	}
+
+	pub fn to_char(self) -> char {
+		match self {
+			Self::Sidewalk => 's',
+			Self::Freewalk => '.',
+			Self::Impasse => '#',
+			Self::Stair => '^',
+			Self::Ramp => '/',
+			Self::Road => 'r',
+		}
+	}
+
+	// the name for this type in csv maps
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Sidewalk => "Sidewalk",
+			Self::Freewalk => "Freewalk",
+			Self::Impasse => "Impasse",
+			Self::Stair => "Stair",
+			Self::Ramp => "Ramp",
+			Self::Road => "Road",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<TileType> {
+		Self::ALL.into_iter().find(|ty| ty.name() == name)
+	}
+
+	pub fn is_wheelchair_accessible(&self) -> bool {
+		!matches!(self, Self::Stair | Self::Impasse)
+	}
+
+	// cost of walking onto a tile of this type, u32::MAX can't be entered
+	pub fn movement_cost(&self) -> u32 {
+		match self {
+			Self::Sidewalk => 1,
+			Self::Freewalk => 2,
+			Self::Ramp => 2,
+			Self::Stair => 3,
+			Self::Road => 4,
+			Self::Impasse => u32::MAX,
+		}
+	}
+}
+
+// edges are always stored by world direction, get and set never look at
+// facing. facing only says which way the tile was turned, and turning a
+// tile moves its edges along with it
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tile {
+	facing: PlaneDir,
+	// indexed by PlaneDir as usize, None for edges never set
+	#[cfg_attr(feature = "serde", serde(with = "edge_map"))]
+	dir_infos: [Option<DirInfo>; 4],
+	// what it was built as, kept through turns and edge edits
+	#[cfg_attr(feature = "serde", serde(default))]
+	ty: TileType,
+}
+
+impl Default for Tile {
+	fn default() -> Tile {
+		Tile::new()
+	}
+}
+
+impl Tile {
+	pub const DEFAULT_FACING: PlaneDir = PlaneDir::North;
+
+	pub fn new() -> Tile {
+		Tile{facing: Self::DEFAULT_FACING, dir_infos: [None; 4], ty: TileType::default()}
+	}
+
+	pub fn from_type(ty: TileType, facing: PlaneDir) -> Tile {
+		Tile{ty: ty, ..Self::from_template(ty.template(), facing)}
+	}
+
+	pub fn from_template(template: TileDirTemplate, facing: PlaneDir) -> Tile {
+		let mut t = Tile{facing: Self::DEFAULT_FACING, dir_infos: [None; 4], ty: template.default_type()};
+		for dir in PlaneDir::ALL {
+			let info = template.dir_info(dir);
+			if info != DirInfo::default() {
+				t.set(&dir, info);
+			}
+		}
+		t.turn_to(facing);
+		return t;
+	}
+
+	// quarter turns clockwise, the edges go round with the facing
+	pub fn turn(&mut self, n: i8) {
+		let before = self.dir_infos;
+		for dir in PlaneDir::ALL {
+			self.dir_infos[dir.rotate(n) as usize] = before[dir as usize];
+		}
+		self.facing = self.facing.rotate(n);
+	}
+
+	pub fn turn_to(&mut self, facing: PlaneDir) {
+		self.turn(self.facing.rotate_diff(facing));
+	}
+
+	// mirrored left to right, East and West edges trade places
+	pub fn flip_horizontal(&mut self) {
+		self.mirror(PlaneDir::flipped_horizontal);
+	}
+
+	// mirrored top to bottom, North and South edges trade places
+	pub fn flip_vertical(&mut self) {
+		self.mirror(PlaneDir::flipped_vertical);
+	}
+
+	fn mirror(&mut self, flip: fn(&PlaneDir) -> PlaneDir) {
+		let before = self.dir_infos;
+		for dir in PlaneDir::ALL {
+			self.dir_infos[flip(&dir) as usize] = before[dir as usize];
+		}
+		self.facing = flip(&self.facing);
+	}
+
+	// only turn changes it, so the edges always go round with it
+	pub fn facing(&self) -> PlaneDir {
+		self.facing
+	}
+
+	pub fn ty(&self) -> TileType {
+		self.ty
+	}
+
+	// only relabels the tile, its edges stay as they are
+	pub fn set_ty(&mut self, ty: TileType) {
+		self.ty = ty;
+	}
+
+	// the edge as walkers see it, open where nothing was set
+	pub fn get(&self, dir: &PlaneDir) -> DirInfo {
+		self.get_raw(dir).unwrap_or_default()
+	}
+
+	// the edge as authored, None if it was never set
+	pub fn get_raw(&self, dir: &PlaneDir) -> Option<DirInfo> {
+		self.dir_infos[*dir as usize]
+	}
+
+	pub fn set(&mut self, dir: &PlaneDir, info: DirInfo) {
+		self.dir_infos[*dir as usize] = Some(info);
+	}
+
+	pub fn movement_cost(&self) -> u32 {
+		self.ty.movement_cost()
+	}
+
+	// nothing gets in from any side
+	pub fn blocks(&self) -> bool {
+		self.edges().all(|(_, info)| info.is_wall())
+	}
+
+	// whether something can come in through the edge on the dir side
+	pub fn is_enterable_from(&self, dir: &PlaneDir) -> bool {
+		!self.get(dir).is_wall()
+	}
+
+	// whether a walker can cross from this tile to other, its neighbour
+	// towards dir on the same layer. both edges have to be open and at
+	// the same height, a step edge leads to another layer instead
+	pub fn connects(&self, dir: &PlaneDir, other: &Tile) -> bool {
+		let out = self.get(dir);
+		let opposite = dir.opposite();
+		return out.enterable &&
+			other.is_enterable_from(&opposite) &&
+			out.elevation_delta == other.get(&opposite).elevation_delta;
+	}
+
+	// what stepping from here through dir onto other costs, the dearer of
+	// the two edges that meet there
+	pub fn crossing_cost(&self, dir: &PlaneDir, other: &Tile) -> u32 {
+		self.get(dir).cost.max(other.get(&dir.opposite()).cost)
+	}
+
+	// all four edges in PlaneDir::ALL order, unset ones as the default
+	pub fn edges(&self) -> impl Iterator<Item = (PlaneDir, DirInfo)> + '_ {
+		PlaneDir::ALL.into_iter().map(|d| (d, self.get(&d)))
+	}
+
+	pub fn same_edges(&self, other: &Tile) -> bool {
+		self.edges().eq(other.edges())
+	}
+}
+
+impl Grid<Tile> {
+	// rotated_cw with every tile turned along with the layer
+	pub fn rotated_tiles_cw(&self) -> Grid<Tile> {
+		self.rearranged(self.height, self.width, |c| Coord{x: self.height - 1 - c.y, y: c.x}, |t| {
+			let mut t = t.clone();
+			t.turn(1);
+			t
+		})
+	}
+
+	// flipped_horizontal with every tile's edges mirrored as well
+	pub fn flipped_tiles_horizontal(&self) -> Grid<Tile> {
+		self.rearranged(self.width, self.height, |c| Coord{x: self.width - 1 - c.x, y: c.y}, |t| {
+			let mut t = t.clone();
+			t.flip_horizontal();
+			t
+		})
+	}
+
+	// flipped_vertical with every tile's edges mirrored as well
+	pub fn flipped_tiles_vertical(&self) -> Grid<Tile> {
+		self.rearranged(self.width, self.height, |c| Coord{x: c.x, y: self.height - 1 - c.y}, |t| {
+			let mut t = t.clone();
+			t.flip_vertical();
+			t
+		})
+	}
+
+	// whether a walker arriving from the from side can get onto coord. an
+	// empty or out of range cell never can, every pathfinder relies on that
+	pub fn can_enter(&self, coord: &Coord, from: PlaneDir) -> bool {
+		self.try_get(coord).ok().and_then(|t| t.as_ref()).is_some_and(|t| t.is_enterable_from(&from))
+	}
+}
+
+// where the tiles of type ty are on a layer, in row-major order
+pub fn tiles_of_type(grid: &Grid<Tile>, ty: TileType) -> Vec<Coord> {
+	grid.find_all(|t| t.ty == ty)
+}
+
+impl<T> Grid<T> {
+	pub fn new(width: usize, height: usize) -> Grid<T> {
+		match Self::try_new(width, height) {
+			Ok(g) => g,
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	// a zero width or height is allowed and gives a grid with no cells,
+	// where every coord is out of bounds
+	pub fn try_new(width: usize, height: usize) -> Result<Grid<T>, GridAllocError> {
+		let len = width.checked_mul(height).ok_or(GridAllocError::TooLarge{width: width, height: height})?;
+		let v = repeat_with(|| None).take(len).collect::<Vec<_>>();
+		return Ok(Grid::<T>{
+			tile_array: v.into_boxed_slice(),
+			width: width,
+			height: height,
+			occupied: Occupancy::new(len),
+		});
+	}
+
+	#[cfg(feature = "serde")]
+	fn rebuild_occupied(&mut self) {
+		self.occupied = Occupancy::new(self.tile_array.len());
+		for i in 0..self.tile_array.len() {
+			self.occupied.set(i, self.tile_array[i].is_some());
+		}
+	}
+
+	fn maybe_coord_to_index(&self, coord: &Coord) -> Result<usize, OutOfBounds> {
+		let out = OutOfBounds{coord: *coord, width: self.width, height: self.height};
+		if coord.x >= self.width ||
+		   coord.y >= self.height {
+			return Err(out);
+		}
+		// only a grid too big to exist can overflow, but don't wrap into the wrong cell
+		let i = coord.y.checked_mul(self.width).and_then(|row| row.checked_add(coord.x)).ok_or(out)?;
+		return Ok(i);
+	}
+
+	fn coord_to_index(&self, coord: &Coord) -> usize {
+		match self.maybe_coord_to_index(coord) {
+			Ok(i) => i,
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	// panics outside the grid, see try_get
+	pub fn get(&self, coord: &Coord) -> &Option<T> {
+		&self.tile_array[self.coord_to_index(coord)]
+	}
+
+	pub fn try_get(&self, coord: &Coord) -> Result<&Option<T>, OutOfBounds> {
+		Ok(&self.tile_array[self.maybe_coord_to_index(coord)?])
+	}
+
+	// inverse of coord_to_index, for walking tile_array directly
+	pub fn index_to_coord(&self, i: usize) -> Coord {
+		assert!(i < self.tile_array.len(), "index {} width {} height {}", i, self.width, self.height);
+		Coord{x: i % self.width, y: i / self.width}
+	}
+
+	pub fn get_by_index(&self, i: usize) -> &Option<T> {
+		&self.tile_array[i]
+	}
+
+	// every cell in row-major order, empty ones included
+	pub fn cells(&self) -> impl Iterator<Item = (Coord, &Option<T>)> {
+		let width = self.width;
+		self.tile_array.iter().enumerate().map(move |(i, t)| (Coord{x: i % width, y: i / width}, t))
+	}
+
+	// only the cells that hold something, row-major
+	pub fn iter(&self) -> impl Iterator<Item = (Coord, &T)> {
+		self.cells().filter_map(|(c, t)| t.as_ref().map(|t| (c, t)))
+	}
+
+	// one line per row and one char per cell, handy in test failures
+	pub fn debug_ascii(&self, render: impl Fn(&Option<T>) -> char) -> String {
+		let rows: Vec<String> = (0..self.height)
+			.map(|y| (0..self.width).map(|x| render(&self.tile_array[y * self.width + x])).collect())
+			.collect();
+		return rows.join("\n");
+	}
+
+	// gives back what was there
+	pub fn remove(&mut self, coord: &Coord) -> Option<T> {
+		let i = self.coord_to_index(coord);
+		self.occupied.set(i, false);
+		return self.tile_array[i].take();
+	}
+
+	// gives back whatever was replaced, None if the cell was empty
+	pub fn add(&mut self, coord: &Coord, t: T) -> Option<T> {
+		let i = self.coord_to_index(coord);
+		self.occupied.set(i, true);
+		return self.tile_array[i].replace(t);
+	}
+
+	// the value at coord, a default one is put there first if it's empty
+	pub fn entry(&mut self, coord: &Coord) -> &mut T where T: Default {
+		let i = self.coord_to_index(coord);
+		self.occupied.set(i, true);
+		return self.tile_array[i].get_or_insert_with(T::default);
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	pub fn count_occupied(&self) -> usize {
+		self.occupied.count()
+	}
+
+	// nothing in any cell
+	pub fn is_empty(&self) -> bool {
+		self.occupied.is_empty()
+	}
+
+	// the first cell holding something in row-major order
+	pub fn first_occupied(&self) -> Option<Coord> {
+		self.occupied.first().map(|i| self.index_to_coord(i))
+	}
+
+	// top left and bottom right corners of the smallest box holding every
+	// occupied cell, None when there aren't any
+	pub fn occupied_bounds(&self) -> Option<(Coord, Coord)> {
+		let mut cells = self.iter().map(|(c, _)| c);
+		let first = cells.next()?;
+		let bounds = cells.fold((first, first), |(min, max), c| {
+			(Coord{x: min.x.min(c.x), y: min.y.min(c.y)}, Coord{x: max.x.max(c.x), y: max.y.max(c.y)})
+		});
+		return Some(bounds);
+	}
+
+	// the first occupied cell in row-major order whose value passes pred
+	pub fn find<F: Fn(&T) -> bool>(&self, pred: F) -> Option<Coord> {
+		self.iter().find(|(_, t)| pred(t)).map(|(c, _)| c)
+	}
+
+	// every occupied cell whose value passes pred, in row-major order
+	pub fn find_all<F: Fn(&T) -> bool>(&self, pred: F) -> Vec<Coord> {
+		self.iter().filter(|(_, t)| pred(t)).map(|(c, _)| c).collect()
+	}
+
+	// empties every cell f says no to, like Vec::retain
+	pub fn retain<F: FnMut(&Coord, &T) -> bool>(&mut self, mut f: F) {
+		for i in 0..self.tile_array.len() {
+			let c = self.index_to_coord(i);
+			if self.tile_array[i].as_ref().is_some_and(|t| !f(&c, t)) {
+				self.tile_array[i] = None;
+				self.occupied.set(i, false);
+			}
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.tile_array.iter_mut().for_each(|t| *t = None);
+		self.occupied.clear();
+	}
+
+	// keeps whatever is still inside the new bounds, anchored at the top left
+	pub fn resize(&mut self, width: usize, height: usize) {
+		let mut resized = Grid::new(width, height);
+		let old = std::mem::take(&mut self.tile_array);
+		for (i, t) in old.into_vec().into_iter().enumerate() {
+			let c = Coord{x: i % self.width, y: i / self.width};
+			if let Some(t) = t && resized.contains(&c) {
+				resized.add(&c, t);
+			}
+		}
+		*self = resized;
+	}
+
+	// quarter turn clockwise, width and height swap and the top left corner
+	// ends up top right. the values themselves aren't touched, see
+	// rotated_tiles_cw for a layer
+	pub fn rotated_cw(&self) -> Grid<T> where T: Clone {
+		self.rearranged(self.height, self.width, |c| Coord{x: self.height - 1 - c.y, y: c.x}, T::clone)
+	}
+
+	// columns reversed, see flipped_tiles_horizontal for a layer
+	pub fn flipped_horizontal(&self) -> Grid<T> where T: Clone {
+		self.rearranged(self.width, self.height, |c| Coord{x: self.width - 1 - c.x, y: c.y}, T::clone)
+	}
+
+	// rows reversed, see flipped_tiles_vertical for a layer
+	pub fn flipped_vertical(&self) -> Grid<T> where T: Clone {
+		self.rearranged(self.width, self.height, |c| Coord{x: c.x, y: self.height - 1 - c.y}, T::clone)
+	}
+
+	// a copy of the width x height block with its top left at origin, all of
+	// which has to be inside this grid
+	pub fn subgrid(&self, origin: &Coord, width: usize, height: usize) -> Result<Grid<T>, OutOfBounds> where T: Clone {
+		let mut out = Grid::new(width, height);
+		if width == 0 || height == 0 {
+			return Ok(out);
+		}
+		let far = Coord{x: origin.x.saturating_add(width - 1), y: origin.y.saturating_add(height - 1)};
+		self.maybe_coord_to_index(&far)?;
+		for y in 0..height {
+			for x in 0..width {
+				if let Some(t) = self.get(&Coord{x: origin.x + x, y: origin.y + y}) {
+					out.add(&Coord{x: x, y: y}, t.clone());
+				}
+			}
+		}
+		return Ok(out);
+	}
+
+	// stamps src's values down with its top left at `at`, empty cells in src
+	// leave what's under them and whatever falls off the edge is dropped
+	pub fn blit(&mut self, src: &Grid<T>, at: &Coord) where T: Clone {
+		for (c, t) in src.iter() {
+			let (Some(x), Some(y)) = (at.x.checked_add(c.x), at.y.checked_add(c.y)) else {
+				continue;
+			};
+			let dest = Coord{x: x, y: y};
+			if self.contains(&dest) {
+				self.add(&dest, t.clone());
+			}
+		}
+	}
+
+	// every value moved to place(its coord) in a new width x height grid
+	fn rearranged(&self, width: usize, height: usize, place: impl Fn(Coord) -> Coord, each: impl Fn(&T) -> T) -> Grid<T> {
+		let mut out = Grid::new(width, height);
+		for (c, t) in self.iter() {
+			out.add(&place(c), each(t));
+		}
+		return out;
+	}
+
+	pub fn swap(&mut self, a: &Coord, b: &Coord) {
+		let (i, j) = (self.coord_to_index(a), self.coord_to_index(b));
+		self.tile_array.swap(i, j);
+		self.occupied.set(i, self.tile_array[i].is_some());
+		self.occupied.set(j, self.tile_array[j].is_some());
+	}
+
+	pub fn contains(&self, coord: &Coord) -> bool {
+		self.maybe_coord_to_index(coord).is_ok()
+	}
+
+	// in bounds and holding something, never panics
+	pub fn occupied(&self, coord: &Coord) -> bool {
+		self.maybe_coord_to_index(coord).is_ok_and(|i| self.occupied.contains(i))
+	}
+
+	// in-bounds neighbours in PlaneDir::ALL order
+	pub fn neighbors(&self, coord: &Coord) -> Vec<Coord> {
+		let mut out = Vec::with_capacity(4);
+		self.neighbors_into(coord, &mut out);
+		return out;
+	}
+
+	// as neighbors, refilling a buffer the caller keeps around
+	pub fn neighbors_into(&self, coord: &Coord, buf: &mut Vec<Coord>) {
+		buf.clear();
+		buf.extend(PlaneDir::ALL.iter().filter_map(|d| coord.step(*d)).filter(|c| self.contains(c)));
+	}
+
+	// in-bounds neighbours and the direction each lies in
+	pub fn neighbors_with_dir(&self, coord: &Coord) -> Vec<(PlaneDir, Coord)> {
+		PlaneDir::ALL.iter()
+			.filter_map(|d| coord.step(*d).map(|c| (*d, c)))
+			.filter(|(_, c)| self.contains(c))
+			.collect()
+	}
+
+	// how many of the 4, or with diagonals 8, cells around coord hold
+	// something. cells off the grid count as full when outside_occupied is
+	// set, so caves generated with it get walled in
+	pub fn occupied_neighbor_count(&self, coord: &Coord, include_diagonals: bool, outside_occupied: bool) -> u8 {
+		let mut count = 0;
+		for dy in -1..=1_isize {
+			for dx in -1..=1_isize {
+				if (dx, dy) == (0, 0) || (!include_diagonals && dx != 0 && dy != 0) {
+					continue;
+				}
+				let c = coord.x.checked_add_signed(dx).zip(coord.y.checked_add_signed(dy)).map(|(x, y)| Coord{x: x, y: y});
+				let full = match c {
+					Some(c) if self.contains(&c) => self.occupied(&c),
+					_ => outside_occupied,
+				};
+				if full {
+					count += 1;
+				}
+			}
+		}
+		return count;
+	}
+
+	// every in-bounds cell, center first and then ring by ring outwards.
+	// each ring goes clockwise from its top left corner
+	pub fn spiral_from(&self, center: Coord) -> impl Iterator<Item = Coord> {
+		let (w, h) = (self.width as isize, self.height as isize);
+		let (cx, cy) = (center.x as isize, center.y as isize);
+		// far enough out to reach the furthest corner
+		let last = cx.max(w - 1 - cx).max(cy).max(h - 1 - cy);
+		(0..=last)
+			.flat_map(move |r| ring(cx, cy, r))
+			.filter(move |&(x, y)| x >= 0 && y >= 0 && x < w && y < h)
+			.map(|(x, y)| Coord{x: x as usize, y: y as usize})
+	}
+
+	// the empty cell fewest rings out from near, near itself if it's empty.
+	// None once the grid's full
+	pub fn nearest_empty(&self, near: &Coord) -> Option<Coord> {
+		self.spiral_from(*near).find(|c| !self.occupied(c))
+	}
+}
+
+// the cells exactly r steps from (cx, cy) counting diagonals as one, in
+// bounds or not
+fn ring(cx: isize, cy: isize, r: isize) -> Vec<(isize, isize)> {
+	if r == 0 {
+		return vec![(cx, cy)];
+	}
+	let mut out = Vec::with_capacity(8 * r as usize);
+	for x in cx - r..=cx + r {
+		out.push((x, cy - r));
+	}
+	for y in cy - r + 1..=cy + r {
+		out.push((cx + r, y));
+	}
+	for x in (cx - r..cx + r).rev() {
+		out.push((x, cy + r));
+	}
+	for y in (cy - r + 1..cy + r).rev() {
+		out.push((cx - r, y));
+	}
+	return out;
+}
+
+// the same cells as a Grid without an Option per cell, the occupancy bits
+// say which ones hold something and empty cells keep T::default(). for
+// Grid<u32> each cell is 8 bytes (size_of::<Option<u32>>()), here 4 plus
+// a bit
+#[derive(Clone, Debug, PartialEq)]
+pub struct DenseGrid<T> {
+	values: Vec<T>,
+	width: usize,
+	height: usize,
+	occupied: Occupancy,
+}
+
+impl<T: Copy + Default> DenseGrid<T> {
+	pub fn new(width: usize, height: usize) -> DenseGrid<T> {
+		match Self::try_new(width, height) {
+			Ok(g) => g,
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	// as Grid::try_new
+	pub fn try_new(width: usize, height: usize) -> Result<DenseGrid<T>, GridAllocError> {
+		let len = width.checked_mul(height).ok_or(GridAllocError::TooLarge{width: width, height: height})?;
+		return Ok(DenseGrid{
+			values: vec![T::default(); len],
+			width: width,
+			height: height,
+			occupied: Occupancy::new(len),
+		});
+	}
+
+	fn coord_to_index(&self, coord: &Coord) -> usize {
+		let i = (coord.x < self.width && coord.y < self.height)
+			.then(|| coord.y.checked_mul(self.width)?.checked_add(coord.x))
+			.flatten();
+		match i {
+			Some(i) => i,
+			None => panic!("coord {:?} width {} height {}", coord, self.width, self.height),
+		}
+	}
+
+	pub fn contains(&self, coord: &Coord) -> bool {
+		coord.x < self.width && coord.y < self.height
+	}
+
+	pub fn get(&self, coord: &Coord) -> Option<T> {
+		let i = self.coord_to_index(coord);
+		self.occupied.contains(i).then(|| self.values[i])
+	}
+
+	pub fn add(&mut self, coord: &Coord, t: T) {
+		let i = self.coord_to_index(coord);
+		self.values[i] = t;
+		self.occupied.set(i, true);
+	}
+
+	pub fn remove(&mut self, coord: &Coord) {
+		let i = self.coord_to_index(coord);
+		self.values[i] = T::default();
+		self.occupied.set(i, false);
+	}
+
+	pub fn count_occupied(&self) -> usize {
+		self.occupied.count()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.occupied.is_empty()
+	}
+
+	pub fn first_occupied(&self) -> Option<Coord> {
+		self.occupied.first().map(|i| Coord{x: i % self.width, y: i / self.width})
+	}
+
+	// only the cells that hold something, row-major
+	pub fn iter(&self) -> impl Iterator<Item = (Coord, T)> {
+		let width = self.width;
+		self.values.iter().enumerate()
+			.filter(|(i, _)| self.occupied.contains(*i))
+			.map(move |(i, t)| (Coord{x: i % width, y: i / width}, *t))
+	}
+
+	pub fn clear(&mut self) {
+		self.values.iter_mut().for_each(|t| *t = T::default());
+		self.occupied.clear();
+	}
+}
+
+impl<T: Copy + Default> From<&Grid<T>> for DenseGrid<T> {
+	fn from(grid: &Grid<T>) -> DenseGrid<T> {
+		let mut dense = DenseGrid::new(grid.width, grid.height);
+		for (c, t) in grid.iter() {
+			dense.add(&c, *t);
+		}
+		return dense;
+	}
+}
+
+// what a layer is called and the rgba tint it's drawn with while another
+// layer is the one being edited
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LayerMeta {
+	pub name: String,
+	pub tint: [f32; 4],
+}
+
+impl LayerMeta {
+	// faded, so the layer being edited stands out over it
+	pub const DEFAULT_TINT: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+
+	// what a layer nobody named gets
+	pub fn unnamed(elevation: Elevation) -> LayerMeta {
+		LayerMeta{name: format!("layer {}", elevation), tint: Self::DEFAULT_TINT}
+	}
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TileMap {
+	// kept in elevation order, lowest first
+	layers: BTreeMap<Elevation, Grid<Tile>>,
+	// only for layers that were given some, see layer_meta
+	#[cfg_attr(feature = "serde", serde(default))]
+	layer_meta: BTreeMap<Elevation, LayerMeta>,
+	width: usize,
+	height: usize,
+	// bumped whenever a layer might have changed, for anything caching results
+	#[cfg_attr(feature = "serde", serde(skip))]
+	version: u64,
+	// different for every map made, loaded or cloned, so a cache can't take
+	// another map at the same version for the one it was made for
+	#[cfg_attr(feature = "serde", serde(skip, default = "next_map_id"))]
+	id: u64,
+	// grids made ahead of time for layers that don't exist yet, layer_mut
+	// takes one of these before allocating
+	#[cfg_attr(feature = "serde", serde(skip))]
+	spare_layers: Vec<Grid<Tile>>,
+}
+
+static NEXT_MAP_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_map_id() -> u64 {
+	NEXT_MAP_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Default for TileMap {
+	fn default() -> TileMap {
+		TileMap::new(0, 0)
+	}
+}
+
+// a copy is a map of its own, edits to it don't reach the original's caches
+impl Clone for TileMap {
+	fn clone(&self) -> TileMap {
+		TileMap{
+			layers: self.layers.clone(),
+			layer_meta: self.layer_meta.clone(),
+			width: self.width,
+			height: self.height,
+			version: self.version,
+			id: next_map_id(),
+			spare_layers: self.spare_layers.clone(),
+		}
+	}
+}
+
+// same contents, however many edits it took to get there
+impl PartialEq for TileMap {
+	fn eq(&self, other: &Self) -> bool {
+		self.layers == other.layers && self.layer_meta == other.layer_meta && self.footprint() == other.footprint()
+	}
+}
+
+// just the size of each layer, a big map's cells would drown everything else
+impl std::fmt::Debug for TileMap {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		struct LayerCounts<'a>(&'a BTreeMap<Elevation, Grid<Tile>>);
+		impl std::fmt::Debug for LayerCounts<'_> {
+			fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+				f.debug_map().entries(self.0.iter().map(|(e, g)| (e.0, g.count_occupied()))).finish()
+			}
+		}
+		f.debug_struct("TileMap")
+			.field("width", &self.width)
+			.field("height", &self.height)
+			.field("version", &self.version)
+			.field("layers", &LayerCounts(&self.layers))
+			.finish()
+	}
+}
+
+impl TileMap {
+	pub fn new(width: usize, height: usize) -> TileMap {
+		TileMap{layers: BTreeMap::new(), layer_meta: BTreeMap::new(), width: width, height: height, version: 0, id: next_map_id(), spare_layers: Vec::new()}
+	}
+
+	// room for layers layers made up front without saying which elevations
+	// they'll be at. the BTreeMap can't reserve, so the grids wait aside
+	pub fn with_layer_capacity(width: usize, height: usize, layers: usize) -> TileMap {
+		let mut map = TileMap::new(width, height);
+		map.reserve_layers(layers);
+		return map;
+	}
+
+	// makes sure another additional layers can be added without allocating
+	pub fn reserve_layers(&mut self, additional: usize) {
+		let (width, height) = self.footprint();
+		let missing = additional.saturating_sub(self.spare_layers.len());
+		self.spare_layers.reserve(missing);
+		self.spare_layers.extend((0..missing).map(|_| Grid::new(width, height)));
+	}
+
+	// how many layers the map can have before it has to allocate another grid
+	pub fn layer_capacity(&self) -> usize {
+		self.layers.len() + self.spare_layers.len()
+	}
+
+	// the given layers made up front, each grid is one allocation
+	pub fn with_layers(width: usize, height: usize, elevations: impl IntoIterator<Item = Elevation>) -> TileMap {
+		let mut map = TileMap::new(width, height);
+		for e in elevations {
+			map.layers.insert(e, Grid::new(width, height));
+		}
+		return map;
+	}
+
+	pub fn version(&self) -> u64 {
+		self.version
+	}
+
+	// which map this is and how far it's been edited, what caches check
+	// before trusting what they worked out
+	pub fn revision(&self) -> (u64, u64) {
+		(self.id, self.version)
+	}
+
+	// every layer shares the same width and height
+	pub fn footprint(&self) -> (usize, usize) {
+		(self.width, self.height)
+	}
+
+	pub fn layer(&self, elevation: Elevation) -> Option<&Grid<Tile>> {
+		self.layers.get(&elevation)
+	}
+
+	pub fn layer_mut(&mut self, elevation: Elevation) -> &mut Grid<Tile> {
+		self.version += 1;
+		let (width, height) = self.footprint();
+		let spare = &mut self.spare_layers;
+		self.layers.entry(elevation).or_insert_with(|| spare.pop().unwrap_or_else(|| Grid::new(width, height)))
+	}
+
+	// the name and tint set for elevation, LayerMeta::unnamed if there aren't any
+	pub fn layer_meta(&self, elevation: Elevation) -> LayerMeta {
+		self.layer_meta.get(&elevation).cloned().unwrap_or_else(|| LayerMeta::unnamed(elevation))
+	}
+
+	// returns what was set before. there doesn't have to be a layer there yet
+	pub fn set_layer_meta(&mut self, elevation: Elevation, meta: LayerMeta) -> Option<LayerMeta> {
+		self.layer_meta.insert(elevation, meta)
+	}
+
+	// lowest and highest elevations that have a layer
+	pub fn elevation_range(&self) -> Option<(Elevation, Elevation)> {
+		let (min, _) = self.layers.first_key_value()?;
+		let (max, _) = self.layers.last_key_value()?;
+		return Some((*min, *max));
+	}
+
+	// Grid::occupied_bounds over every layer at once
+	pub fn occupied_bounds(&self) -> Option<(Coord, Coord)> {
+		self.layers.values().filter_map(|g| g.occupied_bounds()).reduce(|(min, max), (lo, hi)| {
+			(Coord{x: min.x.min(lo.x), y: min.y.min(lo.y)}, Coord{x: max.x.max(hi.x), y: max.y.max(hi.y)})
+		})
+	}
+
+	// every layer from the lowest up
+	pub fn layers(&self) -> impl Iterator<Item = (Elevation, &Grid<Tile>)> {
+		self.layers.iter().map(|(e, g)| (*e, g))
+	}
+
+	// where a walker on the tile at (elevation, coord) can step to. an edge's
+	// elevation_delta says how high it sits above its own tile, and two tiles
+	// meet through their shared edge when both are open and at the same height.
+	// that's how stairs reach into the layer above and back down again
+	pub fn walkable_neighbors(&self, elevation: Elevation, coord: &Coord) -> Vec<(Elevation, Coord)> {
+		let Some(here) = self.get(elevation, coord) else {
+			return Vec::new();
+		};
+		let mut out = Vec::new();
+		for dir in PlaneDir::ALL {
+			let edge = here.get(&dir);
+			let Some(next) = coord.step(dir) else {
+				continue;
+			};
+			if edge.is_wall() {
+				continue;
+			}
+			let height = elevation + edge.elevation_delta;
+			let opposite = dir.opposite();
+			for (e, g) in &self.layers {
+				if !g.can_enter(&next, opposite) {
+					continue;
+				}
+				let there = g.get(&next).as_ref().expect("can_enter only lets tiles through");
+				if *e + there.get(&opposite).elevation_delta == height {
+					out.push((*e, next));
+				}
+			}
+		}
+		return out;
+	}
+
+	pub fn get(&self, elevation: Elevation, coord: &Coord) -> Option<&Tile> {
+		self.layer(elevation).and_then(|g| g.get(coord).as_ref())
+	}
+
+	// see Grid::can_enter, a missing layer can't be entered anywhere
+	pub fn can_enter(&self, elevation: Elevation, coord: &Coord, from: PlaneDir) -> bool {
+		self.layer(elevation).is_some_and(|g| g.can_enter(coord, from))
+	}
+
+	// returns what was there before
+	pub fn set(&mut self, elevation: Elevation, coord: &Coord, tile: Option<Tile>) -> Option<Tile> {
+		let g = self.layer_mut(elevation);
+		return match tile {
+			Some(t) => g.add(coord, t),
+			None => g.remove(coord),
+		};
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::collections::HashMap;
+	use std::panic::catch_unwind;
+
+	#[test]
+	pub fn test_grid() {
+		let g = Grid::<Tile>::new(3, 4);
+		let coords = vec![(1, 7), (3, 2), (8, 9)];
+		for c in &coords {
+			let coord = Coord::from(c);
+			assert_eq!(g.try_get(&coord), Err(OutOfBounds{coord: coord, width: 3, height: 4}));
+		}
+		assert_eq!(g.try_get(&Coord{x: 2, y: 3}), Ok(&None));
+	}
+
+	#[test]
+	pub fn test_occupied() {
+		let mut g = Grid::<u8>::new(3, 4);
+		g.add(&Coord{x: 2, y: 3}, 0);
+		assert!(g.occupied(&Coord{x: 2, y: 3}));
+		assert!(!g.occupied(&Coord{x: 1, y: 3}));
+		assert!(!g.occupied(&Coord{x: 3, y: 0}));
+		assert!(!g.occupied(&Coord{x: 0, y: usize::MAX}));
+		g.remove(&Coord{x: 2, y: 3});
+		assert!(!g.occupied(&Coord{x: 2, y: 3}));
+	}
+
+	#[test]
+	#[should_panic(expected = "coord Coord { x: 3, y: 2 } width 3 height 4")]
+	pub fn test_grid_get_panics() {
+		let g = Grid::<Tile>::new(3, 4);
+		g.get(&Coord{x: 3, y: 2});
+	}
+
+	#[test]
+	#[should_panic(expected = "coord Coord { x: 1, y: 4 } width 3 height 4")]
+	pub fn test_grid_get_panics_past_last_row() {
+		let g = Grid::<Tile>::new(3, 4);
+		g.get(&Coord{x: 1, y: 4});
+	}
+
+	#[test]
+	#[should_panic(expected = "coord Coord { x: 2, y: 0 } width 2 height 2")]
+	pub fn test_grid_add_panics() {
+		// 2 + 0 * 2 is a real index, into the next row, so this one would
+		// wrap rather than run off the array
+		let mut g = Grid::<u8>::new(2, 2);
+		g.add(&Coord{x: 2, y: 0}, 1);
+	}
+
+	#[test]
+	pub fn test_try_new() {
+		let empty: Grid<u8> = Grid::try_new(0, 5).unwrap();
+		assert_eq!(empty.tile_array.len(), 0);
+		assert!(empty.is_empty());
+		assert!(!empty.contains(&Coord{x: 0, y: 0}));
+		assert_eq!(Grid::<u8>::try_new(3, 0).unwrap().cells().count(), 0);
+
+		let too_big = Grid::<u8>::try_new(usize::MAX / 2, 3);
+		assert_eq!(too_big.err(), Some(GridAllocError::TooLarge{width: usize::MAX / 2, height: 3}));
+		assert!(catch_unwind(|| Grid::<u8>::new(usize::MAX, 2)).is_err());
+		assert_eq!(Grid::<u8>::try_new(4, 2).unwrap(), Grid::new(4, 2));
+
+		// the dense one says no the same way
+		assert_eq!(DenseGrid::<u8>::try_new(usize::MAX / 2, 3).err(), Some(GridAllocError::TooLarge{width: usize::MAX / 2, height: 3}));
+		assert!(catch_unwind(|| DenseGrid::<u8>::new(usize::MAX, 2)).is_err());
+		assert_eq!(DenseGrid::<u8>::try_new(4, 2).unwrap(), DenseGrid::new(4, 2));
+		assert!(DenseGrid::<u8>::try_new(0, 5).unwrap().is_empty());
+	}
+
+	#[test]
+	pub fn test_index_overflow() {
+		// never allocated at this size, only the indexing is looked at
+		let g: Grid<u8> = Grid{tile_array: Box::new([]), width: usize::MAX / 2 + 1, height: 3, occupied: Occupancy::new(0)};
+		let c = Coord{x: 1, y: 2};
+		assert_eq!(c.x.wrapping_add(c.y.wrapping_mul(g.width)), 1);
+		assert_eq!(g.maybe_coord_to_index(&c), Err(OutOfBounds{coord: c, width: g.width, height: 3}));
+		assert!(!g.contains(&c));
+		assert!(catch_unwind(|| g.coord_to_index(&c)).is_err());
+		assert_eq!(g.maybe_coord_to_index(&Coord{x: 5, y: 0}), Ok(5));
+
+		let d: DenseGrid<u8> = DenseGrid{values: Vec::new(), width: usize::MAX / 2 + 1, height: 3, occupied: Occupancy::new(0)};
+		assert!(catch_unwind(|| d.coord_to_index(&c)).is_err());
+	}
+
+	#[test]
+	pub fn test_huge_row() {
+		// y * width overflows, so this has to be turned away before any arithmetic
+		let mut g = Grid::<u8>::new(4, 4);
+		g.add(&Coord{x: 0, y: 0}, 1);
+		for c in [Coord{x: 0, y: usize::MAX}, Coord{x: 3, y: usize::MAX / 2 + 1}, Coord{x: usize::MAX, y: usize::MAX}] {
+			assert_eq!(c.y.checked_mul(g.width), None);
+			assert_eq!(g.maybe_coord_to_index(&c), Err(OutOfBounds{coord: c, width: 4, height: 4}));
+			assert_eq!(g.try_get(&c), Err(OutOfBounds{coord: c, width: 4, height: 4}));
+			assert!(!g.occupied(&c));
+		}
+	}
+
+	#[test]
+	pub fn test_rotated_cw() {
+		// 3 wide, 2 high
+		let mut g = Grid::<u8>::new(3, 2);
+		g.add(&Coord{x: 0, y: 0}, 1);
+		g.add(&Coord{x: 2, y: 0}, 2);
+		g.add(&Coord{x: 2, y: 1}, 3);
+		g.add(&Coord{x: 0, y: 1}, 4);
+		let r = g.rotated_cw();
+		assert_eq!((r.width(), r.height()), (2, 3));
+		assert_eq!(r.get(&Coord{x: 1, y: 0}), &Some(1));
+		assert_eq!(r.get(&Coord{x: 1, y: 2}), &Some(2));
+		assert_eq!(r.get(&Coord{x: 0, y: 2}), &Some(3));
+		assert_eq!(r.get(&Coord{x: 0, y: 0}), &Some(4));
+		assert_eq!(r.count_occupied(), 4);
+		assert_eq!(r.rotated_cw().rotated_cw().rotated_cw(), g);
+
+		// and tiles turn with the layer, a wall on a north edge ends up east
+		let mut layer = Grid::<Tile>::new(3, 2);
+		layer.add(&Coord{x: 0, y: 0}, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::North));
+		layer.add(&Coord{x: 2, y: 1}, Tile::from_template(TileDirTemplate::Stair, PlaneDir::West));
+		let turned = layer.rotated_tiles_cw();
+		let wall = turned.get(&Coord{x: 1, y: 0}).as_ref().unwrap();
+		assert_eq!(wall.facing(), PlaneDir::East);
+		assert_eq!(wall.get(&PlaneDir::East), DirInfo::WALL);
+		assert_eq!(wall.get(&PlaneDir::North), DirInfo::OPEN);
+		let stair = turned.get(&Coord{x: 0, y: 2}).as_ref().unwrap();
+		assert_eq!(stair, &Tile::from_template(TileDirTemplate::Stair, PlaneDir::North));
+		let mut back = turned;
+		for _ in 0..3 {
+			back = back.rotated_tiles_cw();
+		}
+		assert_eq!(back, layer);
+	}
+
+	#[test]
+	pub fn test_flipped() {
+		let mut g = Grid::<u8>::new(3, 2);
+		g.add(&Coord{x: 0, y: 0}, 1);
+		g.add(&Coord{x: 1, y: 1}, 2);
+		let h = g.flipped_horizontal();
+		assert_eq!((h.width(), h.height()), (3, 2));
+		assert_eq!(h.get(&Coord{x: 2, y: 0}), &Some(1));
+		assert_eq!(h.get(&Coord{x: 1, y: 1}), &Some(2));
+		let v = g.flipped_vertical();
+		assert_eq!(v.get(&Coord{x: 0, y: 1}), &Some(1));
+		assert_eq!(v.get(&Coord{x: 1, y: 0}), &Some(2));
+		assert_eq!(h.count_occupied(), 2);
+		assert_eq!(v.count_occupied(), 2);
+		assert_eq!(h.flipped_horizontal(), g);
+		assert_eq!(v.flipped_vertical(), g);
+
+		let mut layer = Grid::<Tile>::new(3, 2);
+		layer.add(&Coord{x: 0, y: 0}, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East));
+		layer.add(&Coord{x: 2, y: 1}, Tile::from_template(TileDirTemplate::Stair, PlaneDir::North));
+		let h = layer.flipped_tiles_horizontal();
+		let wall = h.get(&Coord{x: 2, y: 0}).as_ref().unwrap();
+		assert_eq!(wall, &Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::West));
+		// north and south are left alone by a horizontal flip
+		assert_eq!(h.get(&Coord{x: 0, y: 1}).as_ref().unwrap(), &Tile::from_template(TileDirTemplate::Stair, PlaneDir::North));
+		let v = layer.flipped_tiles_vertical();
+		assert_eq!(v.get(&Coord{x: 2, y: 0}).as_ref().unwrap(), &Tile::from_template(TileDirTemplate::Stair, PlaneDir::South));
+		assert_eq!(v.get(&Coord{x: 0, y: 1}).as_ref().unwrap(), &Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East));
+		assert_eq!(h.flipped_tiles_horizontal(), layer);
+		assert_eq!(v.flipped_tiles_vertical(), layer);
+	}
+
+	#[test]
+	pub fn test_subgrid() {
+		let mut g = Grid::<u8>::new(5, 4);
+		for x in 0..5 {
+			for y in 0..4 {
+				if (x + y) % 2 == 0 {
+					g.add(&Coord{x: x, y: y}, (x * 10 + y) as u8);
+				}
+			}
+		}
+		let block = g.subgrid(&Coord{x: 1, y: 1}, 3, 2).unwrap();
+		assert_eq!((block.width(), block.height()), (3, 2));
+		assert_eq!(block.get(&Coord{x: 0, y: 0}), &Some(11));
+		assert_eq!(block.get(&Coord{x: 1, y: 0}), &None);
+		assert_eq!(block.get(&Coord{x: 2, y: 0}), &Some(31));
+		assert_eq!(block.get(&Coord{x: 1, y: 1}), &Some(22));
+		assert_eq!(block.count_occupied(), 3);
+
+		// the whole thing and nothing at all are both fine
+		assert_eq!(g.subgrid(&Coord{x: 0, y: 0}, 5, 4).unwrap(), g);
+		assert!(g.subgrid(&Coord{x: 4, y: 3}, 0, 0).unwrap().is_empty());
+
+		assert_eq!(g.subgrid(&Coord{x: 3, y: 1}, 3, 2), Err(OutOfBounds{coord: Coord{x: 5, y: 2}, width: 5, height: 4}));
+		assert_eq!(g.subgrid(&Coord{x: 0, y: 3}, 1, 2), Err(OutOfBounds{coord: Coord{x: 0, y: 4}, width: 5, height: 4}));
+		assert!(g.subgrid(&Coord{x: usize::MAX, y: 0}, 2, 1).is_err());
+	}
+
+	#[test]
+	pub fn test_blit() {
+		let mut g = Grid::<u8>::new(4, 3);
+		g.add(&Coord{x: 3, y: 2}, 9);
+		g.add(&Coord{x: 2, y: 1}, 8);
+		let mut stamp = Grid::<u8>::new(2, 3);
+		stamp.add(&Coord{x: 0, y: 0}, 1);
+		stamp.add(&Coord{x: 1, y: 1}, 2);
+		stamp.add(&Coord{x: 1, y: 2}, 3);
+
+		// hangs off the right and bottom, the empty (0, 1) keeps the 8 under it
+		g.blit(&stamp, &Coord{x: 2, y: 0});
+		assert_eq!(g.get(&Coord{x: 2, y: 0}), &Some(1));
+		assert_eq!(g.get(&Coord{x: 3, y: 1}), &Some(2));
+		assert_eq!(g.get(&Coord{x: 3, y: 2}), &Some(3));
+		assert_eq!(g.get(&Coord{x: 2, y: 1}), &Some(8));
+		assert_eq!(g.count_occupied(), 4);
+
+		g.blit(&stamp, &Coord{x: 3, y: 2});
+		assert_eq!(g.get(&Coord{x: 3, y: 2}), &Some(1));
+		assert_eq!(g.count_occupied(), 4);
+		// entirely off the grid does nothing
+		let before = g.clone();
+		g.blit(&stamp, &Coord{x: usize::MAX, y: 0});
+		assert_eq!(g, before);
+	}
+
+	#[test]
+	pub fn test_find() {
+		let mut g = Grid::<Tile>::new(4, 3);
+		for x in 0..4 {
+			g.add(&Coord{x: x, y: 0}, Tile::from_type(TileType::Sidewalk, PlaneDir::North));
+		}
+		g.add(&Coord{x: 2, y: 1}, Tile::from_type(TileType::Road, PlaneDir::North));
+		g.add(&Coord{x: 1, y: 2}, Tile::from_type(TileType::Road, PlaneDir::North));
+		g.add(&Coord{x: 3, y: 2}, Tile::from_type(TileType::Stair, PlaneDir::North));
+
+		assert_eq!(g.find(|t| t.ty == TileType::Stair), Some(Coord{x: 3, y: 2}));
+		assert_eq!(g.find(|t| t.ty == TileType::Road), Some(Coord{x: 2, y: 1}));
+		assert_eq!(g.find_all(|t| t.ty == TileType::Road), vec![Coord{x: 2, y: 1}, Coord{x: 1, y: 2}]);
+		assert_eq!(g.find(|t| t.ty == TileType::Ramp), None);
+		assert!(g.find_all(|t| t.ty == TileType::Ramp).is_empty());
+		assert_eq!(Grid::<Tile>::new(2, 2).find(|_| true), None);
+	}
+
+	#[test]
+	pub fn test_spiral_from() {
+		let g = Grid::<u8>::new(5, 4);
+		let center = Coord{x: 1, y: 1};
+		let order: Vec<Coord> = g.spiral_from(center).collect();
+		assert_eq!(order[0], center);
+		assert_eq!(&order[1..9], &[
+			Coord{x: 0, y: 0}, Coord{x: 1, y: 0}, Coord{x: 2, y: 0}, Coord{x: 2, y: 1},
+			Coord{x: 2, y: 2}, Coord{x: 1, y: 2}, Coord{x: 0, y: 2}, Coord{x: 0, y: 1},
+		]);
+		for pair in order.windows(2) {
+			assert!(center.chebyshev_distance(&pair[0]) <= center.chebyshev_distance(&pair[1]));
+		}
+		// each cell once, the parts of rings off the grid skipped
+		let seen: std::collections::HashSet<Coord> = order.iter().copied().collect();
+		assert_eq!((order.len(), seen.len()), (20, 20));
+
+		let corner: Vec<Coord> = g.spiral_from(Coord{x: 4, y: 3}).take(4).collect();
+		assert_eq!(corner, vec![Coord{x: 4, y: 3}, Coord{x: 3, y: 2}, Coord{x: 4, y: 2}, Coord{x: 3, y: 3}]);
+		assert_eq!(Grid::<u8>::new(0, 0).spiral_from(Coord{x: 0, y: 0}).count(), 0);
+	}
+
+	#[test]
+	pub fn test_nearest_empty() {
+		let mut g = Grid::<u8>::new(5, 5);
+		let near = Coord{x: 2, y: 2};
+		assert_eq!(g.nearest_empty(&near), Some(near));
+
+		// near and its whole first ring but one taken
+		for c in g.spiral_from(near).take(9).collect::<Vec<_>>() {
+			g.add(&c, 1);
+		}
+		g.remove(&Coord{x: 1, y: 3});
+		assert_eq!(g.nearest_empty(&near), Some(Coord{x: 1, y: 3}));
+		g.add(&Coord{x: 1, y: 3}, 1);
+		let found = g.nearest_empty(&near).unwrap();
+		assert_eq!(near.chebyshev_distance(&found), 2);
+		assert_eq!(found, Coord{x: 0, y: 0});
+
+		let mut full = Grid::<u8>::new(2, 1);
+		full.add(&Coord{x: 0, y: 0}, 1);
+		full.add(&Coord{x: 1, y: 0}, 1);
+		assert_eq!(full.nearest_empty(&Coord{x: 0, y: 0}), None);
+	}
+
+	#[test]
+	pub fn test_occupied_neighbor_count() {
+		// #.#
+		// .##
+		// #..
+		let mut g = Grid::<u8>::new(3, 3);
+		for c in [(0, 0), (2, 0), (1, 1), (2, 1), (0, 2)] {
+			g.add(&Coord::from(&c), 1);
+		}
+		let center = Coord{x: 1, y: 1};
+		assert_eq!(g.occupied_neighbor_count(&center, false, false), 1);
+		assert_eq!(g.occupied_neighbor_count(&center, true, false), 4);
+		// nothing around the center is off the grid
+		assert_eq!(g.occupied_neighbor_count(&center, true, true), 4);
+
+		let edge = Coord{x: 2, y: 2};
+		assert_eq!(g.occupied_neighbor_count(&edge, false, false), 1);
+		assert_eq!(g.occupied_neighbor_count(&edge, true, false), 2);
+		assert_eq!(g.occupied_neighbor_count(&edge, false, true), 3);
+		assert_eq!(g.occupied_neighbor_count(&edge, true, true), 7);
+
+		let top = Coord{x: 1, y: 0};
+		assert_eq!(g.occupied_neighbor_count(&top, false, false), 3);
+		assert_eq!(g.occupied_neighbor_count(&top, true, true), 7);
+	}
+
+	#[test]
+	pub fn test_debug_ascii() {
+		let mut g = Grid::<Tile>::new(4, 3);
+		g.add(&Coord{x: 0, y: 0}, Tile::from_type(TileType::Impasse, PlaneDir::North));
+		g.add(&Coord{x: 1, y: 1}, Tile::from_type(TileType::Stair, PlaneDir::North));
+		g.add(&Coord{x: 3, y: 1}, Tile::from_type(TileType::Road, PlaneDir::North));
+		g.add(&Coord{x: 2, y: 2}, Tile::from_type(TileType::Freewalk, PlaneDir::North));
+		let dump = g.debug_ascii(|t| t.as_ref().map_or(' ', |t| t.ty.to_char()));
+		assert_eq!(dump, "#   \n ^ r\n  . ");
+		assert_eq!(dump.lines().count(), g.height());
+
+		let marks = g.debug_ascii(|t| if t.is_some() { 'x' } else { '-' });
+		assert_eq!(marks, "x---\n-x-x\n--x-");
+	}
+
+	#[test]
+	pub fn test_occupied_bounds() {
+		let mut g = Grid::<u8>::new(10, 8);
+		assert_eq!(g.occupied_bounds(), None);
+		g.add(&Coord{x: 7, y: 1}, 1);
+		assert_eq!(g.occupied_bounds(), Some((Coord{x: 7, y: 1}, Coord{x: 7, y: 1})));
+		// scattered over the top right quarter
+		g.add(&Coord{x: 5, y: 3}, 2);
+		g.add(&Coord{x: 9, y: 2}, 3);
+		g.add(&Coord{x: 6, y: 0}, 4);
+		assert_eq!(g.occupied_bounds(), Some((Coord{x: 5, y: 0}, Coord{x: 9, y: 3})));
+		g.remove(&Coord{x: 9, y: 2});
+		assert_eq!(g.occupied_bounds(), Some((Coord{x: 5, y: 0}, Coord{x: 7, y: 3})));
+	}
+
+	#[test]
+	pub fn test_tiles_of_type() {
+		let mut g = Grid::<Tile>::new(4, 3);
+		let types = [TileType::Road, TileType::Sidewalk, TileType::Road, TileType::Impasse, TileType::Freewalk];
+		for (i, ty) in types.into_iter().cycle().take(10).enumerate() {
+			g.add(&Coord{x: i % 4, y: i / 4}, Tile::from_type(ty, PlaneDir::North));
+		}
+		// the type is kept whichever way the tile faces
+		g.add(&Coord{x: 3, y: 2}, Tile::from_type(TileType::Road, PlaneDir::West));
+		assert_eq!(tiles_of_type(&g, TileType::Road), vec![
+			Coord{x: 0, y: 0}, Coord{x: 2, y: 0}, Coord{x: 1, y: 1}, Coord{x: 3, y: 1}, Coord{x: 3, y: 2},
+		]);
+		assert_eq!(tiles_of_type(&g, TileType::Impasse), vec![Coord{x: 3, y: 0}, Coord{x: 0, y: 2}]);
+		assert!(tiles_of_type(&g, TileType::Stair).is_empty());
+	}
+
+	#[test]
+	pub fn test_retain() {
+		let mut g = Grid::<u8>::new(5, 3);
+		for x in 0..5 {
+			for y in 0..3 {
+				if x != 2 || y != 1 {
+					g.add(&Coord{x: x, y: y}, (x + y) as u8);
+				}
+			}
+		}
+		let mut looked_at = 0;
+		g.retain(|c, _| {
+			looked_at += 1;
+			c.x % 2 == 0
+		});
+		// empty cells aren't asked about
+		assert_eq!(looked_at, 14);
+		assert_eq!(g.count_occupied(), 8);
+		assert!(g.iter().all(|(c, _)| c.x % 2 == 0));
+		assert!(g.occupied(&Coord{x: 4, y: 2}) && !g.occupied(&Coord{x: 3, y: 2}));
+		assert_eq!(g.first_occupied(), Some(Coord{x: 0, y: 0}));
+
+		g.retain(|_, v| *v > 3);
+		assert_eq!(g.find_all(|_| true), vec![Coord{x: 4, y: 0}, Coord{x: 4, y: 1}, Coord{x: 2, y: 2}, Coord{x: 4, y: 2}]);
+	}
+
+	#[test]
+	pub fn test_entry() {
+		let mut g = Grid::<Tile>::new(2, 2);
+		let c = Coord{x: 0, y: 1};
+		assert_eq!(g.entry(&c), &Tile::new());
+		assert!(g.occupied(&c));
+		g.entry(&c).ty = TileType::Road;
+		assert_eq!(g.entry(&c).ty, TileType::Road);
+		assert_eq!(g.count_occupied(), 1);
+
+		// a cell that already has something keeps it
+		let mut counts = Grid::<u32>::new(3, 1);
+		counts.add(&Coord{x: 2, y: 0}, 5);
+		*counts.entry(&Coord{x: 2, y: 0}) += 1;
+		*counts.entry(&Coord{x: 0, y: 0}) += 1;
+		assert_eq!(counts.get(&Coord{x: 2, y: 0}), &Some(6));
+		assert_eq!(counts.get(&Coord{x: 0, y: 0}), &Some(1));
+	}
+
+	#[test]
+	#[should_panic(expected = "coord Coord { x: 2, y: 2 } width 2 height 2")]
+	pub fn test_entry_panics() {
+		let mut g = Grid::<Tile>::new(2, 2);
+		g.entry(&Coord{x: 2, y: 2});
+	}
+
+	#[test]
+	pub fn test_add_replaces() {
+		let mut g = Grid::<u8>::new(2, 2);
+		let c = Coord{x: 1, y: 1};
+		assert_eq!(g.add(&c, 3), None);
+		assert_eq!(g.add(&c, 4), Some(3));
+		assert_eq!(g.get(&c), &Some(4));
+		assert_eq!(g.remove(&c), Some(4));
+		assert_eq!(g.remove(&c), None);
+		assert!(g.is_empty());
+	}
+
+	#[test]
+	pub fn test_add_and_get() {
+		let mut g = Grid::<u8>::new(2, 3);
+		let coord = Coord{x: 1, y: 0};
+		let val = 7u8;
+		g.add(&coord, val);
+		match g.get(&coord) {
+			Some(i) => assert_eq!(*i, val),
+			None => panic!("expected {} at {:?}", val, coord),
+		};
+	}
+
+	#[test]
+	pub fn test_neighbors_into() {
+		let g: Grid<u8> = Grid::new(4, 3);
+		let mut buf = vec![Coord{x: 9, y: 9}];
+		for c in [Coord{x: 0, y: 0}, Coord{x: 3, y: 1}, Coord{x: 1, y: 1}, Coord{x: 3, y: 2}] {
+			g.neighbors_into(&c, &mut buf);
+			assert_eq!(buf, g.neighbors(&c));
+			let with_dir: Vec<Coord> = g.neighbors_with_dir(&c).into_iter().map(|(_, n)| n).collect();
+			assert_eq!(buf, with_dir);
+		}
+		assert_eq!(g.neighbors(&Coord{x: 1, y: 1}).len(), 4);
+		assert_eq!(g.neighbors(&Coord{x: 0, y: 0}), vec![Coord{x: 1, y: 0}, Coord{x: 0, y: 1}]);
+	}
+
+	#[test]
+	pub fn test_occupancy() {
+		let check = |g: &Grid<u32>| {
+			let scan: Vec<Coord> = g.cells().filter(|(_, t)| t.is_some()).map(|(c, _)| c).collect();
+			assert_eq!(g.count_occupied(), scan.len());
+			assert_eq!(g.is_empty(), scan.is_empty());
+			assert_eq!(g.first_occupied(), scan.first().copied());
+			for (i, t) in g.tile_array.iter().enumerate() {
+				assert_eq!(g.occupied.contains(i), t.is_some());
+			}
+		};
+		let mut g: Grid<u32> = Grid::new(13, 11);
+		check(&g);
+		for i in 0..60 {
+			let c = Coord{x: (i * 7) % 13, y: (i * 5) % 11};
+			if i % 3 == 2 {
+				g.remove(&c);
+			} else {
+				g.add(&c, i as u32);
+			}
+			check(&g);
+		}
+		g.swap(&Coord{x: 0, y: 0}, &Coord{x: 12, y: 10});
+		check(&g);
+		g.swap(&Coord{x: 1, y: 1}, &Coord{x: 2, y: 1});
+		check(&g);
+
+		g.resize(5, 20);
+		assert_eq!((g.width, g.height), (5, 20));
+		check(&g);
+		g.resize(9, 3);
+		check(&g);
+
+		g.clear();
+		check(&g);
+		assert!(g.is_empty());
+	}
+
+	#[test]
+	pub fn test_dense_grid() {
+		let mut sparse: Grid<u32> = Grid::new(9, 7);
+		let mut dense: DenseGrid<u32> = DenseGrid::new(9, 7);
+		let same = |sparse: &Grid<u32>, dense: &DenseGrid<u32>| {
+			for (c, t) in sparse.cells() {
+				assert_eq!(dense.get(&c), *t);
+			}
+			assert_eq!(dense.count_occupied(), sparse.count_occupied());
+			assert_eq!(dense.is_empty(), sparse.is_empty());
+			assert_eq!(dense.first_occupied(), sparse.first_occupied());
+			assert!(dense.iter().eq(sparse.iter().map(|(c, t)| (c, *t))));
+		};
+		same(&sparse, &dense);
+		for i in 0..40usize {
+			let c = Coord{x: (i * 4) % 9, y: (i * 3) % 7};
+			// 0 is the default, it still has to read back as a value
+			let v = (i % 5) as u32;
+			if i % 4 == 3 {
+				sparse.remove(&c);
+				dense.remove(&c);
+			} else {
+				sparse.add(&c, v);
+				dense.add(&c, v);
+			}
+			same(&sparse, &dense);
+		}
+		assert_eq!(DenseGrid::from(&sparse), dense);
+		assert!(catch_unwind(|| dense.get(&Coord{x: 9, y: 0})).is_err());
+		dense.clear();
+		assert!(dense.is_empty() && dense.get(&Coord{x: 0, y: 0}).is_none());
+
+		assert_eq!(std::mem::size_of::<Option<u32>>(), 2 * std::mem::size_of::<u32>());
+	}
+
+	#[test]
+	pub fn test_rotate() {
+		assert_eq!(PlaneDir::North.clockwise(), PlaneDir::East);
+		assert_eq!(PlaneDir::East.clockwise(), PlaneDir::South);
+		assert_eq!(PlaneDir::South.clockwise(), PlaneDir::West);
+		assert_eq!(PlaneDir::West.clockwise(), PlaneDir::North);
+		assert_eq!(PlaneDir::North.anticlockwise(), PlaneDir::West);
+		assert_eq!(PlaneDir::East.anticlockwise(), PlaneDir::North);
+		assert_eq!(PlaneDir::South.anticlockwise(), PlaneDir::East);
+		assert_eq!(PlaneDir::West.anticlockwise(), PlaneDir::South);
+		assert_eq!(PlaneDir::North.rotate_diff(PlaneDir::South), 2);
+		assert_eq!(PlaneDir::East.rotate_diff(PlaneDir::North), -1);
+
+		let diff = PlaneDir::West.rotate_diff(PlaneDir::South);
+		assert_eq!(PlaneDir::West.rotate(diff), PlaneDir::South);
+
+		// rows are from, columns are to, both in ALL order
+		let raw = [
+			[0, 1, 2, 3],
+			[-1, 0, 1, 2],
+			[-2, -1, 0, 1],
+			[-3, -2, -1, 0],
+		];
+		let shortest = [
+			[0, 1, 2, -1],
+			[-1, 0, 1, 2],
+			[2, -1, 0, 1],
+			[1, 2, -1, 0],
+		];
+		for (i, from) in PlaneDir::ALL.iter().enumerate() {
+			for (j, to) in PlaneDir::ALL.iter().enumerate() {
+				assert_eq!(from.rotate_diff(*to), raw[i][j], "{:?} to {:?}", from, to);
+				assert_eq!(from.shortest_rotate_diff(*to), shortest[i][j], "{:?} to {:?}", from, to);
+				assert_eq!(from.rotate(shortest[i][j]), *to);
+			}
+		}
+
+		for dir in PlaneDir::ALL {
+			for n in [i8::MIN, i8::MIN + 1, -101, -8, -5, 0, 4, 9, 100, i8::MAX - 1, i8::MAX] {
+				let expected = PlaneDir::try_from(((dir as i32) + n as i32).rem_euclid(4) as u8).unwrap();
+				assert_eq!(dir.rotate(n), expected, "{:?} by {}", dir, n);
+			}
+		}
+		assert_eq!(PlaneDir::East.rotate(i8::MAX), PlaneDir::North);
+		assert_eq!(PlaneDir::North.rotate(i8::MIN), PlaneDir::North);
+	}
+
+	#[test]
+	pub fn test_offset() {
+		for dir in PlaneDir::ALL {
+			let (dx, dy) = dir.offset();
+			assert_eq!(PlaneDir::from_offset(dx, dy), Some(dir));
+			assert_eq!(dir.opposite().offset(), (-dx, -dy));
+		}
+		assert_eq!(PlaneDir::from_offset(0, 0), None);
+		assert_eq!(PlaneDir::from_offset(1, 1), None);
+		assert_eq!(PlaneDir::from_offset(0, 2), None);
+	}
+
+	#[test]
+	pub fn test_tile_type_chars() {
+		let types = [TileType::Sidewalk, TileType::Freewalk, TileType::Impasse, TileType::Stair, TileType::Ramp, TileType::Road];
+		for ty in types {
+			assert_eq!(TileType::from_char(ty.to_char()), Some(ty));
+		}
+		assert_eq!(TileType::from_char(' '), None);
+	}
+
+	#[test]
+	pub fn test_from_template() {
+		let t = Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::West);
+		assert_eq!(t.facing, PlaneDir::West);
+		assert_eq!(t.get(&PlaneDir::West), DirInfo::WALL);
+		assert_eq!(t.get(&PlaneDir::North), DirInfo::OPEN);
+
+		let s = Tile::from_template(TileDirTemplate::Stair, PlaneDir::East);
+		assert_eq!(s.get(&PlaneDir::East).elevation_delta, ElevationDelta(1));
+		assert!(!s.get(&PlaneDir::North).enterable);
+		assert!(!s.get(&PlaneDir::South).enterable);
+		assert_eq!(s.get(&PlaneDir::West), DirInfo::OPEN);
+
+		assert_eq!(Tile::from_template(TileDirTemplate::Floor, PlaneDir::North), Tile::new());
+	}
+
+	#[test]
+	pub fn test_index_traversal() {
+		let mut g: Grid<usize> = Grid::new(7, 5);
+		for c in [Coord{x: 0, y: 0}, Coord{x: 6, y: 0}, Coord{x: 3, y: 2}, Coord{x: 6, y: 4}] {
+			g.add(&c, c.x * 10 + c.y);
+		}
+
+		let mut by_coord = Vec::new();
+		for y in 0..g.height {
+			for x in 0..g.width {
+				let c = Coord{x: x, y: y};
+				by_coord.push((c, *g.get(&c)));
+			}
+		}
+		let by_index: Vec<_> = (0..g.tile_array.len()).map(|i| (g.index_to_coord(i), *g.get_by_index(i))).collect();
+		assert_eq!(by_coord, by_index);
+		assert_eq!(g.cells().map(|(c, t)| (c, *t)).collect::<Vec<_>>(), by_coord);
+
+		for (i, (c, _)) in by_index.iter().enumerate() {
+			assert_eq!(g.coord_to_index(c), i);
+		}
+		let held: Vec<_> = g.iter().map(|(c, t)| (c, *t)).collect();
+		assert_eq!(held, vec![(Coord{x: 0, y: 0}, 0), (Coord{x: 6, y: 0}, 60), (Coord{x: 3, y: 2}, 32), (Coord{x: 6, y: 4}, 64)]);
+	}
+
+	#[test]
+	pub fn test_map_occupied_bounds() {
+		let mut map = TileMap::with_layers(8, 8, [Elevation(0), Elevation(3)]);
+		assert_eq!(map.occupied_bounds(), None);
+		map.set(Elevation(0), &Coord{x: 2, y: 5}, Some(Tile::new()));
+		map.set(Elevation(3), &Coord{x: 6, y: 1}, Some(Tile::new()));
+		assert_eq!(map.occupied_bounds(), Some((Coord{x: 2, y: 1}, Coord{x: 6, y: 5})));
+	}
+
+	#[test]
+	pub fn test_layer_meta() {
+		let mut map = TileMap::with_layers(2, 2, [Elevation(0), Elevation(1)]);
+		assert_eq!(map.layer_meta(Elevation(1)), LayerMeta{name: "layer 1".to_string(), tint: LayerMeta::DEFAULT_TINT});
+
+		let mezzanine = LayerMeta{name: "Mezzanine".to_string(), tint: [0.2, 0.4, 1.0, 0.5]};
+		assert_eq!(map.set_layer_meta(Elevation(1), mezzanine.clone()), None);
+		assert_eq!(map.layer_meta(Elevation(1)), mezzanine);
+		assert_eq!(map.layer_meta(Elevation(0)), LayerMeta::unnamed(Elevation(0)));
+		assert_ne!(map, TileMap::with_layers(2, 2, [Elevation(0), Elevation(1)]));
+	}
+
+	#[test]
+	pub fn test_elevation_arithmetic() {
+		let ground = Elevation(0);
+		let up = ElevationDelta(1);
+		assert_eq!(ground + up, Elevation(1));
+		assert_eq!(ground + -up, Elevation(-1));
+		assert_eq!(Elevation(3) - Elevation(5), ElevationDelta(-2));
+		assert_eq!(up + up + ElevationDelta(-3), ElevationDelta(-1));
+		let mut e = Elevation(2);
+		e += ElevationDelta(-4);
+		assert_eq!(e, Elevation(-2));
+		assert_eq!(e.abs_diff(Elevation(3)), 5);
+		assert_eq!(e.to_string(), "-2");
+		assert!(Elevation(-1) < ground);
+		// climbing a stair's delta and taking the difference gets it back
+		assert_eq!((ground + up) - ground, up);
+	}
+
+	#[test]
+	pub fn test_headless_map() {
+		// nothing here needs a window, only the library is built for this
+		let mut map = TileMap::with_layers(4, 3, [Elevation(0)]);
+		for x in 0..4 {
+			map.set(Elevation(0), &Coord{x: x, y: 1}, Some(Tile::from_type(TileType::Sidewalk, PlaneDir::North)));
+		}
+		map.set(Elevation(0), &Coord{x: 2, y: 0}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		assert_eq!(map.footprint(), (4, 3));
+		assert_eq!(map.layer(Elevation(0)).unwrap().count_occupied(), 5);
+		assert_eq!(map.get(Elevation(0), &Coord{x: 3, y: 1}).map(|t| t.ty), Some(TileType::Sidewalk));
+		assert!(!map.can_enter(Elevation(0), &Coord{x: 2, y: 0}, PlaneDir::South));
+		assert_eq!(map.walkable_neighbors(Elevation(0), &Coord{x: 0, y: 1}), vec![(Elevation(0), Coord{x: 1, y: 1})]);
+		let route = path::astar(map.layer(Elevation(0)).unwrap(), Coord{x: 0, y: 1}, Coord{x: 3, y: 1});
+		assert_eq!(route.map(|r| r.len()), Some(4));
+	}
+
+	#[test]
+	pub fn test_with_layers() {
+		let map = TileMap::with_layers(6, 4, (-1..=2).map(Elevation));
+		assert_eq!(map.elevation_range(), Some((Elevation(-1), Elevation(2))));
+		assert!(map.layers().all(|(_, g)| g.tile_array.len() == 24 && g.is_empty()));
+		assert_eq!(map.version(), 0);
+	}
+
+	#[test]
+	pub fn test_with_layer_capacity() {
+		let mut map = TileMap::with_layer_capacity(6, 4, 3);
+		assert!(map.layer_capacity() >= 3);
+		assert_eq!(map.layers().count(), 0);
+
+		// new layers use up the spares rather than growing past them
+		map.set(Elevation(0), &Coord{x: 1, y: 1}, Some(Tile::new()));
+		map.layer_mut(Elevation(4));
+		assert_eq!(map.layer_capacity(), 3);
+		assert_eq!(map.spare_layers.len(), 1);
+		assert!(map.layer(Elevation(4)).is_some_and(|g| g.tile_array.len() == 24 && g.is_empty()));
+		assert!(map == {
+			let mut plain = TileMap::new(6, 4);
+			plain.set(Elevation(0), &Coord{x: 1, y: 1}, Some(Tile::new()));
+			plain.layer_mut(Elevation(4));
+			plain
+		});
+
+		// reserving counts what's already spare
+		map.reserve_layers(2);
+		assert_eq!(map.layer_capacity(), 4);
+		map.reserve_layers(1);
+		assert_eq!(map.layer_capacity(), 4);
+	}
+
+	#[test]
+	pub fn test_tilemap_debug() {
+		let mut map = TileMap::with_layers(40, 30, [-1, 2].map(Elevation));
+		for x in 0..5 {
+			map.set(Elevation(-1), &Coord{x: x, y: 0}, Some(Tile::new()));
+		}
+		map.set(Elevation(2), &Coord{x: 3, y: 3}, Some(Tile::new()));
+		let text = format!("{:?}", map);
+		assert!(text.contains("width: 40, height: 30"), "{}", text);
+		assert!(text.contains("layers: {-1: 5, 2: 1}"), "{}", text);
+		// nothing per cell
+		assert!(text.len() < 100, "{}", text);
+
+		assert_eq!(map.clone(), map);
+		let empty = TileMap::default();
+		assert_eq!(empty.footprint(), (0, 0));
+		assert!(format!("{:?}", empty).contains("layers: {}"));
+	}
+
+	#[test]
+	pub fn test_layer_order() {
+		let mut map = TileMap::new(2, 2);
+		for e in [4, -2, 0, 7, -5, 1] {
+			map.layer_mut(Elevation(e));
+		}
+		let order: Vec<Elevation> = map.layers().map(|(e, _)| e).collect();
+		assert_eq!(order, [-5, -2, 0, 1, 4, 7].map(Elevation));
+		assert_eq!(map.elevation_range(), Some((Elevation(-5), Elevation(7))));
+		assert_eq!(TileMap::new(1, 1).elevation_range(), None);
+	}
+
+	#[test]
+	pub fn test_turn() {
+		let mut wall = Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::North);
+		assert_eq!(wall.get(&PlaneDir::North), DirInfo::WALL);
+		wall.turn_to(PlaneDir::East);
+		assert_eq!(wall.facing, PlaneDir::East);
+		assert_eq!(wall.get(&PlaneDir::East), DirInfo::WALL);
+		assert_eq!(wall.get(&PlaneDir::North), DirInfo::OPEN);
+		assert_eq!(wall, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East));
+
+		// all the way round is back where it started
+		let stair = Tile::from_template(TileDirTemplate::Stair, PlaneDir::South);
+		let mut turned = stair.clone();
+		for _ in 0..4 {
+			turned.turn(1);
+		}
+		assert_eq!(turned, stair);
+		turned.turn(-1);
+		assert_eq!(turned, Tile::from_template(TileDirTemplate::Stair, PlaneDir::East));
+	}
+
+	#[test]
+	pub fn test_dir_info_classifiers() {
+		assert!(DirInfo::OPEN.is_open() && !DirInfo::OPEN.is_wall() && !DirInfo::OPEN.is_step());
+		// cost doesn't stop an edge being plain floor
+		assert!(DirInfo{cost: 4, ..DirInfo::OPEN}.is_open());
+
+		assert!(DirInfo::WALL.is_wall() && !DirInfo::WALL.is_open() && !DirInfo::WALL.is_step());
+
+		let up = DirInfo{elevation_delta: ElevationDelta(1), ..DirInfo::OPEN};
+		let down = DirInfo{elevation_delta: ElevationDelta(-1), ..DirInfo::OPEN};
+		for step in [up, down] {
+			assert!(step.is_step() && !step.is_open() && !step.is_wall());
+		}
+		let walled_step = DirInfo{elevation_delta: ElevationDelta(1), ..DirInfo::WALL};
+		assert!(walled_step.is_wall() && walled_step.is_step() && !walled_step.is_open());
+	}
+
+	#[test]
+	pub fn test_edges() {
+		let mut tile = Tile::from_template(TileDirTemplate::Stair, PlaneDir::East);
+		tile.set(&PlaneDir::South, DirInfo{cost: 2, ..DirInfo::WALL});
+		let up = DirInfo{elevation_delta: ElevationDelta(1), ..DirInfo::OPEN};
+		assert_eq!(tile.edges().collect::<Vec<_>>(), vec![
+			(PlaneDir::North, DirInfo::WALL),
+			(PlaneDir::East, up),
+			(PlaneDir::South, DirInfo{cost: 2, ..DirInfo::WALL}),
+			// never set, comes out as the default
+			(PlaneDir::West, DirInfo::OPEN),
+		]);
+		assert_eq!(tile.get_raw(&PlaneDir::West), None);
+	}
+
+	#[test]
+	pub fn test_tile_type() {
+		let mut road = Tile::from_type(TileType::Road, PlaneDir::East);
+		assert_eq!(road.ty(), TileType::Road);
+		assert_eq!(road.movement_cost(), 4);
+		road.turn(1);
+		road.set(&PlaneDir::North, DirInfo::WALL);
+		assert_eq!(road.ty(), TileType::Road);
+
+		road.set_ty(TileType::Sidewalk);
+		assert_eq!((road.ty(), road.movement_cost()), (TileType::Sidewalk, 1));
+		assert_eq!(road.get(&PlaneDir::North), DirInfo::WALL);
+
+		// from a template it gets the template's usual type
+		assert_eq!(Tile::from_template(TileDirTemplate::Stair, PlaneDir::North).ty(), TileType::Stair);
+		assert_eq!(Tile::new().ty(), TileType::default());
+	}
+
+	#[test]
+	pub fn test_get_raw() {
+		let mut tile = Tile::new();
+		tile.set(&PlaneDir::East, DirInfo::OPEN);
+		assert_eq!(tile.get(&PlaneDir::East), tile.get(&PlaneDir::West));
+		assert_eq!(tile.get_raw(&PlaneDir::East), Some(DirInfo::OPEN));
+		assert_eq!(tile.get_raw(&PlaneDir::West), None);
+		assert!(tile.same_edges(&Tile::new()));
+		assert_ne!(tile, Tile::new());
+
+		// from_template only sets the edges that aren't plain open
+		let wall = Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::South);
+		assert_eq!(wall.get_raw(&PlaneDir::South), Some(DirInfo::WALL));
+		assert_eq!(wall.get_raw(&PlaneDir::North), None);
+
+		#[cfg(feature = "serde")]
+		{
+			let text = ron::to_string(&tile).unwrap();
+			assert!(text.contains("East") && !text.contains("West"));
+			assert_eq!(ron::from_str::<Tile>(&text).unwrap(), tile);
+		}
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_stair_round_trip() {
+		let mut stair = Tile::from_type(TileType::Stair, PlaneDir::West);
+		// every edge set, South to plain open so it's kept apart from unset
+		stair.set(&PlaneDir::East, DirInfo::OPEN);
+		stair.set(&PlaneDir::North, DirInfo{cost: 3, ..DirInfo::WALL});
+		for dir in PlaneDir::ALL {
+			assert!(stair.get_raw(&dir).is_some());
+		}
+
+		let text = ron::to_string(&stair).unwrap();
+		assert!(text.contains("facing:West") && text.contains("cost:3"));
+		assert_eq!(text.matches("cost").count(), 1);
+		let back = ron::from_str::<Tile>(&text).unwrap();
+		assert_eq!(back, stair);
+		assert_eq!(back.facing(), PlaneDir::West);
+		assert_eq!(back.get(&PlaneDir::West).elevation_delta, ElevationDelta(1));
+
+		let json = serde_json::to_string(&stair).unwrap();
+		assert_eq!(serde_json::from_str::<Tile>(&json).unwrap(), stair);
+		let info: DirInfo = serde_json::from_str(r#"{"elevation_delta": -2, "enterable": true}"#).unwrap();
+		assert_eq!(info, DirInfo{elevation_delta: ElevationDelta(-2), ..DirInfo::OPEN});
+	}
+
+	#[test]
+	pub fn test_tile_edges() {
+		// checked against a map of the edges set so far, as tiles used to keep them
+		let infos = [DirInfo::WALL, DirInfo::OPEN, DirInfo{elevation_delta: ElevationDelta(2), ..DirInfo::OPEN}, DirInfo{elevation_delta: ElevationDelta(-1), ..DirInfo::WALL}, DirInfo{cost: 5, ..DirInfo::OPEN}];
+		let mut tile = Tile::new();
+		let mut model: HashMap<PlaneDir, DirInfo> = HashMap::new();
+		for i in 0..24 {
+			let dir = PlaneDir::ALL[(i * 7) % 4];
+			let info = infos[(i * 5) % infos.len()];
+			tile.set(&dir, info);
+			model.insert(dir, info);
+			for d in PlaneDir::ALL {
+				assert_eq!(tile.get(&d), model.get(&d).copied().unwrap_or_default());
+			}
+		}
+		assert_eq!(Tile::new().get(&PlaneDir::East), DirInfo::OPEN);
+
+		// the old map gave level and enterable for any edge missing from it.
+		// each row sets one edge, the other three have to read as missing
+		let old_default = DirInfo{elevation_delta: ElevationDelta(0), enterable: true, cost: DirInfo::DEFAULT_COST};
+		let table = [
+			(PlaneDir::North, DirInfo::WALL),
+			(PlaneDir::East, DirInfo{elevation_delta: ElevationDelta(1), ..DirInfo::OPEN}),
+			(PlaneDir::South, DirInfo{elevation_delta: ElevationDelta(-3), enterable: false, cost: 2}),
+			(PlaneDir::West, DirInfo{cost: 7, ..DirInfo::OPEN}),
+		];
+		for (dir, info) in table {
+			let mut tile = Tile::new();
+			assert!(PlaneDir::ALL.iter().all(|d| tile.get(d) == old_default && tile.get_raw(d).is_none()));
+			tile.set(&dir, info);
+			for d in PlaneDir::ALL {
+				let expected = if d == dir { info } else { old_default };
+				assert_eq!(tile.get(&d), expected, "set {:?}, read {:?}", dir, d);
+			}
+		}
+
+		// the edges live inline, a tile is its four edges, its type and its
+		// facing and owns nothing on the heap
+		use std::mem::{align_of, size_of};
+		let inline = size_of::<[Option<DirInfo>; 4]>() + size_of::<TileType>() + size_of::<PlaneDir>();
+		assert!(size_of::<Tile>() <= inline.next_multiple_of(align_of::<Tile>()), "{} bytes", size_of::<Tile>());
+		assert!(!std::mem::needs_drop::<Tile>());
+	}
+}
@@ -0,0 +1,185 @@
+// mistakes that load and save fine but leave a map behaving oddly, worth
+// a look before saving
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Coord, Elevation, PlaneDir, TileMap};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintWarning {
+	// the tile at coord and the one past its dir edge, on the same layer,
+	// both step through the edge between them but to different heights
+	MismatchedSteps{elevation: Elevation, coord: Coord, dir: PlaneDir},
+	// an open edge going up or down with no tile there to step onto
+	DanglingStep{elevation: Elevation, coord: Coord, dir: PlaneDir},
+	// walkable tiles cut off from the biggest area of the map. coord is the
+	// first of them, lowest layer first and then in row-major order
+	IsolatedRegion{elevation: Elevation, coord: Coord, tiles: usize},
+}
+
+impl fmt::Display for LintWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::MismatchedSteps{elevation, coord, dir} =>
+				write!(f, "({}, {}) on layer {} steps {:?} to a different height than its neighbour", coord.x, coord.y, elevation, dir),
+			Self::DanglingStep{elevation, coord, dir} =>
+				write!(f, "({}, {}) on layer {} steps {:?} onto nothing", coord.x, coord.y, elevation, dir),
+			Self::IsolatedRegion{elevation, coord, tiles} =>
+				write!(f, "{} tiles from ({}, {}) on layer {} can't be reached from the rest", tiles, coord.x, coord.y, elevation),
+		}
+	}
+}
+
+// every warning for the map, edges first and then regions
+pub fn lint(map: &TileMap) -> Vec<LintWarning> {
+	let mut out = Vec::new();
+	for (elevation, grid) in map.layers() {
+		for (coord, tile) in grid.iter() {
+			let joined = map.walkable_neighbors(elevation, &coord);
+			for (dir, edge) in tile.edges() {
+				if edge.is_wall() || !edge.is_step() {
+					continue;
+				}
+				let next = coord.step(dir).filter(|c| grid.contains(c));
+				if !next.is_some_and(|n| joined.iter().any(|(_, c)| *c == n)) {
+					out.push(LintWarning::DanglingStep{elevation: elevation, coord: coord, dir: dir});
+				}
+				// each pair once, from the tile on its north or west side
+				if dir != PlaneDir::East && dir != PlaneDir::South {
+					continue;
+				}
+				let Some(other) = next.and_then(|n| grid.get(&n).as_ref()) else {
+					continue;
+				};
+				let theirs = other.get(&dir.opposite());
+				if !theirs.is_wall() && theirs.is_step() && theirs.elevation_delta != edge.elevation_delta {
+					out.push(LintWarning::MismatchedSteps{elevation: elevation, coord: coord, dir: dir});
+				}
+			}
+		}
+	}
+	out.extend(isolated_regions(map));
+	return out;
+}
+
+// joins walkable tiles into regions and reports all but the biggest
+fn isolated_regions(map: &TileMap) -> Vec<LintWarning> {
+	let mut ids: HashMap<(Elevation, Coord), usize> = HashMap::new();
+	let mut tiles = Vec::new();
+	for (e, g) in map.layers() {
+		for (c, _) in g.iter().filter(|(_, t)| !t.blocks()) {
+			ids.insert((e, c), tiles.len());
+			tiles.push((e, c));
+		}
+	}
+
+	// union find, a one way edge still joins both sides
+	let mut parent: Vec<usize> = (0..tiles.len()).collect();
+	for (i, (e, c)) in tiles.iter().enumerate() {
+		for n in map.walkable_neighbors(*e, c) {
+			if let Some(j) = ids.get(&n) {
+				let (a, b) = (root(&mut parent, i), root(&mut parent, *j));
+				parent[a.max(b)] = a.min(b);
+			}
+		}
+	}
+	// the smallest index in each region is its root, so regions come out
+	// in the order of their first tile
+	let mut sizes: Vec<(usize, usize)> = Vec::new();
+	let mut slot: HashMap<usize, usize> = HashMap::new();
+	for i in 0..tiles.len() {
+		let r = root(&mut parent, i);
+		let s = *slot.entry(r).or_insert_with(|| {
+			sizes.push((r, 0));
+			sizes.len() - 1
+		});
+		sizes[s].1 += 1;
+	}
+
+	let Some(biggest) = sizes.iter().map(|(_, n)| *n).max() else {
+		return Vec::new();
+	};
+	let main = sizes.iter().position(|(_, n)| *n == biggest).expect("biggest is one of them");
+	return sizes.iter().enumerate()
+		.filter(|(i, _)| *i != main)
+		.map(|(_, (r, n))| {
+			let (elevation, coord) = tiles[*r];
+			LintWarning::IsolatedRegion{elevation: elevation, coord: coord, tiles: *n}
+		})
+		.collect();
+}
+
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+	while parent[i] != i {
+		parent[i] = parent[parent[i]];
+		i = parent[i];
+	}
+	return i;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::path::test::grid_from_rows;
+	use crate::{DirInfo, ElevationDelta, Tile, TileDirTemplate};
+
+	#[test]
+	pub fn test_dangling_stair() {
+		let mut map = TileMap::new(3, 3);
+		map.layers.insert(Elevation(0), grid_from_rows(&[
+			"...",
+			"...",
+			"...",
+		]));
+		let stair = Tile::from_template(TileDirTemplate::Stair, PlaneDir::North);
+		map.set(Elevation(0), &Coord{x: 1, y: 1}, Some(stair));
+		assert_eq!(lint(&map), vec![LintWarning::DanglingStep{elevation: Elevation(0), coord: Coord{x: 1, y: 1}, dir: PlaneDir::North}]);
+
+		// a landing on the layer above takes it
+		map.set(Elevation(1), &Coord{x: 1, y: 0}, Some(Tile::from_template(TileDirTemplate::Floor, PlaneDir::North)));
+		assert_eq!(lint(&map), vec![]);
+
+		// one pointing off the map has nowhere to go either
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(Tile::from_template(TileDirTemplate::Stair, PlaneDir::West)));
+		assert!(lint(&map).contains(&LintWarning::DanglingStep{elevation: Elevation(0), coord: Coord{x: 0, y: 0}, dir: PlaneDir::West}));
+	}
+
+	#[test]
+	pub fn test_isolated_room() {
+		let mut map = TileMap::new(6, 2);
+		map.layers.insert(Elevation(0), grid_from_rows(&[
+			"...#..",
+			"...#..",
+		]));
+		assert_eq!(lint(&map), vec![LintWarning::IsolatedRegion{elevation: Elevation(0), coord: Coord{x: 4, y: 0}, tiles: 4}]);
+
+		// a gap in the wall joins them up
+		map.set(Elevation(0), &Coord{x: 3, y: 1}, Some(Tile::from_template(TileDirTemplate::Floor, PlaneDir::North)));
+		assert_eq!(lint(&map), vec![]);
+
+		// a tile alone on another layer is its own region
+		map.set(Elevation(2), &Coord{x: 0, y: 0}, Some(Tile::new()));
+		assert_eq!(lint(&map), vec![LintWarning::IsolatedRegion{elevation: Elevation(2), coord: Coord{x: 0, y: 0}, tiles: 1}]);
+	}
+
+	#[test]
+	pub fn test_mismatched_steps() {
+		let mut map = TileMap::new(2, 1);
+		let step = |d| {
+			let mut t = Tile::new();
+			t.set(&PlaneDir::East, DirInfo{elevation_delta: ElevationDelta(d), ..DirInfo::OPEN});
+			t.set(&PlaneDir::West, DirInfo{elevation_delta: ElevationDelta(d), ..DirInfo::OPEN});
+			t
+		};
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(step(1)));
+		map.set(Elevation(0), &Coord{x: 1, y: 0}, Some(step(2)));
+		let warnings = lint(&map);
+		assert!(warnings.contains(&LintWarning::MismatchedSteps{elevation: Elevation(0), coord: Coord{x: 0, y: 0}, dir: PlaneDir::East}));
+		assert_eq!(warnings.iter().filter(|w| matches!(w, LintWarning::MismatchedSteps{..})).count(), 1);
+
+		// stepping to the same height they meet
+		map.set(Elevation(0), &Coord{x: 1, y: 0}, Some(step(1)));
+		assert!(!lint(&map).iter().any(|w| matches!(w, LintWarning::MismatchedSteps{..})));
+	}
+}
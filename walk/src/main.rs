@@ -1,208 +1,416 @@
-use std::collections::HashMap;
-use std::iter::repeat_with;
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 
 use macroquad::prelude::*;
 use miniquad::conf::{Platform, WebGLVersion};
 
-type Elevation = i32;
-type ElevationDelta = i32;
+use poubelle::agent::{self, Agent};
+use poubelle::editor::{self, Editor, EditorState};
+use poubelle::input::{Action, Button, InputEvent, InputState, MouseFrame};
+use poubelle::lint;
+use poubelle::mapfile;
+use poubelle::{Coord, Grid, PlaneDir, Tile, TileDirTemplate, TileMap, TileType};
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-struct Coord {
-	x: usize,
-	y: usize,
-}
+// tiles per second
+const AGENT_SPEED: f32 = 4.0;
+// agents room is made for before the first one is sent
+const AGENT_CAPACITY: usize = 64;
+// where copied regions are shared between sessions
+const CLIPBOARD_PATH: &str = "clipboard.ron";
+// the layer, camera and template from last time, whichever map was open
+const SESSION_PATH: &str = "session.ron";
+// keys to use in place of the defaults, see KeyBindings::apply
+const KEYS_PATH: &str = "keys.ron";
 
-impl From<&(usize, usize)> for Coord {
-	fn from(input: &(usize, usize)) -> Self {
-		Coord{x: input.0, y: input.1}
+// usage: poubelle [map.ron]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<PathBuf>, &'static str> {
+	// skip the program name
+	args.next();
+	let path = args.next().map(PathBuf::from);
+	if args.next().is_some() {
+		return Err("too many arguments, usage: poubelle [map.ron]");
 	}
+	return Ok(path);
 }
 
-#[derive(Debug)]
-struct Grid<T>{
-	tile_array: Box<[Option<T>]>,
-	width: usize,
-	height: usize,
+fn initial_map(path: Option<&Path>) -> TileMap {
+	let empty = || TileMap::new(48, 32);
+	let Some(path) = path else {
+		return empty();
+	};
+	match mapfile::load_map(path) {
+		// there'd be nothing to draw or click on
+		Ok(map) if map.footprint().0 == 0 || map.footprint().1 == 0 => {
+			let (width, height) = map.footprint();
+			error!("{:?} has no tiles, {}x{}", path, width, height);
+			return empty();
+		},
+		Ok(map) => return map,
+		Err(e) => {
+			error!("couldn't load {:?}: {}", path, e);
+			return empty();
+		},
+	}
 }
 
-#[repr(u8)]
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-enum PlaneDir {
-	North = 0u8,
-	East = 1u8,
-	South = 2u8,
-	West = 3u8,
+// defaults if there's no session yet or it can't be read
+fn initial_session(path: &Path) -> EditorState {
+	match mapfile::load_session(path) {
+		Ok(state) => return state,
+		Err(mapfile::MapParseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => return EditorState::default(),
+		Err(e) => {
+			error!("couldn't load {:?}, starting fresh: {}", path, e);
+			return EditorState::default();
+		},
+	}
 }
 
-impl TryFrom<u8> for PlaneDir {
-	type Error = &'static str;
-
-	fn try_from(val: u8) -> Result<Self, Self::Error> {
-		match val {
-			0 => Ok(Self::North),
-			1 => Ok(Self::East),
-			2 => Ok(Self::South),
-			3 => Ok(Self::West),
-			_ => Err("bad value for PlaneDir")
-		}
+// the defaults, with whatever the keys file changes. it doesn't have to exist
+fn initial_bindings(path: &Path) -> KeyBindings {
+	let mut bindings = KeyBindings::default();
+	match mapfile::load_key_overrides(path) {
+		Ok(overrides) => {
+			for name in bindings.apply(&overrides) {
+				error!("{:?} has a key {:?} that isn't known, keeping the defaults for it", path, name);
+			}
+		},
+		Err(mapfile::MapParseError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => (),
+		Err(e) => error!("couldn't load {:?}, using the default keys: {}", path, e),
 	}
+	return bindings;
 }
 
-impl PlaneDir {
-	// gives x s.t. self.rotate(x) == other
-	pub fn rotate_diff(&self, other: PlaneDir) -> i8 {
-		(other as i8) - (*self as i8)
-	}
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::collections::HashMap;
+	use poubelle::Elevation;
 
-	pub fn rotate(&self, n: i8) -> PlaneDir {
-		PlaneDir::try_from(((*self as i8) + (n as i8)).rem_euclid(4) as u8).expect("unreachable")
+	#[test]
+	pub fn test_screen_to_tile() {
+		let display = Display::new(1280.0, 720.0, 48, 32);
+		let camera = GridCamera{target: Vec2::new(-40.0, 25.0), zoom: 1.75};
+		for coord in [Coord{x: 0, y: 0}, Coord{x: 7, y: 3}, Coord{x: 47, y: 26}] {
+			let world = display.tile_rect(&coord).center();
+			let screen = camera.world_to_screen(world);
+			assert_eq!(camera.screen_to_tile(&display, (screen.x, screen.y)), Some(coord));
+		}
+		assert_eq!(camera.screen_to_tile(&display, (-500.0, -500.0)), None);
 	}
 
-	pub fn clockwise(&self) -> PlaneDir {
-		self.rotate(1)
+	#[test]
+	#[should_panic(expected = "can't lay out a 0x5 grid")]
+	pub fn test_display_zero_tiles() {
+		Display::new(1280.0, 720.0, 0, 5);
 	}
 
-	pub fn anticlockwise(&self) -> PlaneDir {
-		self.rotate(-1)
+	#[test]
+	pub fn test_tile_coord_edges() {
+		let display = Display::new(1280.0, 720.0, 10, 3);
+		let r = display.grid_rect;
+		assert_eq!(display.get_tile_coord_from_pos((r.x, r.y)), Some(Coord{x: 0, y: 0}));
+		assert_eq!(display.get_tile_coord_from_pos((r.x + r.w, r.y + 1.0)), None);
+		assert_eq!(display.get_tile_coord_from_pos((r.x + r.w - 0.01, r.y + 1.0)), Some(Coord{x: 9, y: 0}));
+		assert_eq!(display.get_tile_coord_from_pos((r.x + 1.0, r.y + r.h)), None);
+
+		// three rows don't fill the rect, what's left under them is nothing
+		let last_row = r.y + 3.0 * display.tile_side_len;
+		assert_eq!(display.get_tile_coord_from_pos((r.x + 1.0, last_row - 0.01)), Some(Coord{x: 0, y: 2}));
+		assert_eq!(display.get_tile_coord_from_pos((r.x + 1.0, last_row)), None);
+		assert_eq!(display.get_tile_coord_from_pos((r.x + 1.0, r.y + r.h - 0.01)), None);
 	}
-}
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-struct DirInfo {
-	elevation_delta: ElevationDelta,
-	enterable: bool,
-}
+	#[test]
+	pub fn test_hover_snaps_while_panning() {
+		let display = Display::new(1280.0, 720.0, 48, 32);
+		let cursor = (640.0, 360.0);
+		let camera_tile = |camera: GridCamera| camera.screen_to_tile(&display, cursor);
+		let mut last: Option<Coord> = None;
+		// panned right a few pixels at a time
+		for step in 0..12 {
+			let camera = GridCamera{target: Vec2::new(step as f32 * 7.5, 40.0), zoom: 1.5};
+			let (c, rect) = camera.hovered_tile(&display, cursor).unwrap();
+			let world = camera.screen_to_world(Vec2::new(cursor.0, cursor.1));
+			assert_eq!(Some(c), display.get_tile_coord_from_pos((world.x, world.y)));
+			// the highlight sits on the tile, not wherever the cursor is in it
+			let centre = camera.world_to_screen(display.tile_rect(&c).center());
+			assert!((rect.center() - centre).length() < 0.01);
+			assert!((rect.w - display.tile_rect(&c).w * camera.zoom).abs() < 0.01);
+			// the cursor is over the tile's cell, margin included
+			let slack = Display::TILE_MARGIN * camera.zoom + 0.01;
+			assert!((rect.center().x - cursor.0).abs() <= rect.w / 2.0 + slack);
+			assert!((rect.center().y - cursor.1).abs() <= rect.h / 2.0 + slack);
+			// panning right only ever moves the hovered tile right
+			if let Some(prev) = last {
+				assert!(c.x >= prev.x && c.x <= prev.x + 1);
+			}
+			last = Some(c);
+		}
+		assert_ne!(last, camera_tile(GridCamera{target: Vec2::new(0.0, 40.0), zoom: 1.5}));
 
-enum TileDirTemplate {
-	Floor,
-	SingleWall,
-	Impasse,
-	Stair,
-	Ramp,
-}
+		// the same at any zoom
+		for zoom in [1.0, 1.5, 2.25, 4.0] {
+			let camera = GridCamera{target: Vec2::new(100.0, 40.0), zoom: zoom};
+			let (c, rect) = camera.hovered_tile(&display, cursor).unwrap();
+			assert!((rect.center() - camera.world_to_screen(display.tile_rect(&c).center())).length() < 0.01);
+		}
 
-enum TileType {
-	Sidewalk,
-	Freewalk,
-	Impasse,
-	Stair,
-	Ramp,
-	Road,
-}
+		let camera = GridCamera{target: Vec2::new(-40.0, 25.0), zoom: 2.0};
+		let (c, rect) = camera.hovered_tile(&display, (200.0, 100.0)).unwrap();
+		assert_eq!(c, Coord{x: 1, y: 1});
+		assert_eq!(rect, camera.world_rect_to_screen(display.tile_rect(&Coord{x: 1, y: 1})));
+		assert_eq!(camera.hovered_tile(&display, (-10.0, -10.0)), None);
+	}
 
-#[derive(Debug)]
-struct Tile {
-	facing: PlaneDir,
-	dir_infos: HashMap<PlaneDir, DirInfo>,
-}
+	#[test]
+	pub fn test_restored_camera() {
+		let camera = GridCamera::restored((30.0, -12.5), 2.0);
+		assert_eq!((camera.target, camera.zoom), (Vec2::new(30.0, -12.5), 2.0));
+		// a zoom that would break screen_to_world is thrown away
+		assert_eq!(GridCamera::restored((30.0, 0.0), 0.0), GridCamera::new());
+		assert_eq!(GridCamera::restored((f32::NAN, 0.0), 1.0), GridCamera::new());
+	}
 
-impl Tile {
-	pub const DEFAULT_FACING: PlaneDir = PlaneDir::North;
+	#[test]
+	pub fn test_fit_to() {
+		let viewport = Vec2::new(1280.0, 720.0);
+		let display = Display::new(viewport.x, viewport.y, 48, 32);
+		let bounds = display.span_rect(&Coord{x: 4, y: 2}, &Coord{x: 11, y: 5});
+		let camera = GridCamera::fit_to(bounds, viewport);
+		// wider than the screen's shape, so it spans the width exactly
+		let left = camera.world_to_screen(bounds.point());
+		let right = camera.world_to_screen(bounds.point() + bounds.size());
+		assert!(left.x.abs() < 0.01 && (right.x - viewport.x).abs() < 0.01);
+		assert!(left.y > 0.0 && right.y < viewport.y);
+		assert!((camera.world_to_screen(bounds.center()) - viewport / 2.0).length() < 0.01);
+		assert_eq!(camera.screen_to_tile(&display, (1.0, viewport.y / 2.0 - 20.0)), Some(Coord{x: 4, y: 3}));
 
-	pub fn new() -> Tile {
-		Tile{facing: Self::DEFAULT_FACING, dir_infos: HashMap::new()}
+		// a tall one is fitted by height instead
+		let tall = Rect::new(100.0, 50.0, 20.0, 180.0);
+		let camera = GridCamera::fit_to(tall, viewport);
+		assert_eq!(camera.zoom, 4.0);
+		assert!((camera.world_to_screen(tall.point()).y).abs() < 0.01);
+		assert_eq!(GridCamera::fit_to(Rect::new(10.0, 10.0, 0.0, 5.0), viewport).zoom, 1.0);
 	}
 
-	pub fn get(&self, dir: &PlaneDir) -> DirInfo {
-		match self.dir_infos.get(dir) {
-			Some(i) => *i,
-			None => DirInfo{elevation_delta: 0, enterable: true},
+	#[test]
+	pub fn test_blit_top_left() {
+		let size = Vec2::new(1280.0, 720.0);
+		let display = Display::new(size.x, size.y, 10, 3);
+		let top_left = display.grid_rect.point() + Vec2::splat(0.5);
+		let cameras = [GridCamera::new(), GridCamera{target: Vec2::new(-40.0, 25.0), zoom: 2.0}];
+		// the window the same shape as the target, with bars at the sides and with bars top and bottom
+		for (window_w, window_h) in [(1280.0, 720.0), (2000.0, 720.0), (800.0, 600.0)] {
+			let frame = letterbox_rect(window_w, window_h, size.x, size.y);
+			for camera in cameras {
+				assert!(Blit::new(&camera, size).flip_y);
+				// where the tile's top left pixel ends up in the window, and back the
+				// way the mouse goes
+				let screen = camera.world_to_screen(top_left);
+				let window = frame.point() + screen * (frame.size() / size);
+				let mp = window_to_target(&frame, size, (window.x, window.y));
+				assert!((Vec2::new(mp.0, mp.1) - screen).length() < 0.001);
+				assert_eq!(camera.screen_to_tile(&display, mp), Some(Coord{x: 0, y: 0}));
+				// a bar left or above the grid isn't a tile
+				assert_eq!(camera.screen_to_tile(&display, window_to_target(&frame, size, (frame.x - 1.0, frame.y - 1.0))), None);
+			}
 		}
 	}
-}
 
-impl<T> Grid<T> {
-	pub fn new(width: usize, height: usize) -> Grid<T> {
-		let v = repeat_with(|| None).take(width * height).collect::<Vec<_>>();
-		Grid::<T>{
-			tile_array: v.into_boxed_slice(),
-			width: width,
-			height: height,
-		}
+	#[test]
+	pub fn test_app_config() {
+		let conf = AppConfig::default().conf();
+		assert_eq!(conf.window_title, "pourquoi");
+		assert_eq!(conf.platform.webgl_version, WebGLVersion::WebGL2);
+		assert_eq!((conf.window_width, conf.window_height), (Conf::default().window_width, Conf::default().window_height));
+		assert_eq!(AppConfig::from_vars(|_| None), AppConfig::default());
+
+		let vars = HashMap::from([
+			("POUBELLE_TITLE", "poubelle"),
+			("POUBELLE_WIDTH", "1280"),
+			("POUBELLE_HEIGHT", " 720 "),
+			("POUBELLE_WEBGL", "1"),
+		]);
+		let conf = AppConfig::from_vars(|name| vars.get(name).map(|v| v.to_string())).conf();
+		assert_eq!(conf.window_title, "poubelle");
+		assert_eq!((conf.window_width, conf.window_height), (1280, 720));
+		assert_eq!(conf.platform.webgl_version, WebGLVersion::WebGL1);
+
+		// nonsense is ignored rather than opening a broken window
+		let bad = HashMap::from([("POUBELLE_WIDTH", "wide"), ("POUBELLE_HEIGHT", "-5"), ("POUBELLE_WEBGL", "3")]);
+		assert_eq!(AppConfig::from_vars(|name| bad.get(name).map(|v| v.to_string())), AppConfig::default());
 	}
 
-	fn maybe_coord_to_index(&self, coord: &Coord) -> Result<usize, ()> {
-		let i = coord.x + (coord.y * self.width);
-		if coord.x >= self.width ||
-		   coord.y >= self.height {
-			return Err(());
-		}
-		return Ok(i);
+	#[test]
+	pub fn test_letterbox_rect() {
+		// exactly the target's shape fills the window
+		assert_eq!(letterbox_rect(1280.0, 720.0, 1280.0, 720.0), Rect::new(0.0, 0.0, 1280.0, 720.0));
+		assert_eq!(letterbox_rect(640.0, 360.0, 1280.0, 720.0), Rect::new(0.0, 0.0, 640.0, 360.0));
+
+		// wider, bars left and right
+		let r = letterbox_rect(2000.0, 720.0, 1280.0, 720.0);
+		assert_eq!(r, Rect::new(360.0, 0.0, 1280.0, 720.0));
+
+		// taller, bars top and bottom
+		let r = letterbox_rect(800.0, 600.0, 1280.0, 720.0);
+		assert_eq!((r.x, r.w, r.h), (0.0, 800.0, 450.0));
+		assert_eq!(r.y, 75.0);
+		assert!((r.w / r.h - 16.0 / 9.0).abs() < 1e-5);
+
+		// and back again, the frame's corners are the target's
+		let target = Vec2::new(1280.0, 720.0);
+		assert_eq!(window_to_target(&r, target, (0.0, 75.0)), (0.0, 0.0));
+		assert_eq!(window_to_target(&r, target, (800.0, 525.0)), (1280.0, 720.0));
+		assert_eq!(window_to_target(&r, target, (400.0, 300.0)), (640.0, 360.0));
 	}
 
-	fn coord_to_index(&self, coord: &Coord) -> usize {
-		match self.maybe_coord_to_index(coord) {
-			Ok(i) => i,
-			Err(_) => panic!("coord {:?} width {} height {}", coord, self.width, self.height),
+	#[test]
+	pub fn test_empty_tile_color() {
+		let c = Display::EMPTY_TILE_COLOR;
+		for channel in [c.r, c.g, c.b, c.a] {
+			assert!((0.0..=1.0).contains(&channel), "{:?}", c);
 		}
+		assert_eq!(c, Color::from_rgba(220, 220, 220, 255));
 	}
 
-	pub fn get(&self, coord: &Coord) -> &Option<T> {
-		&self.tile_array[self.coord_to_index(coord)]
+	#[test]
+	pub fn test_empty_layer_skipped() {
+		let display = Display::new(1280.0, 720.0, 6, 4);
+		let mut g: Grid<Tile> = Grid::new(6, 4);
+		let (mut drawn, mut bg) = (0, 0);
+		display.visit_layer(&g, |_, _| drawn += 1);
+		display.visit_bg(Some(&g), |_| bg += 1);
+		display.visit_bg(None, |_| bg += 1);
+		assert_eq!((drawn, bg), (0, 0));
+
+		// one tile and the whole background is drawn cell by cell
+		g.add(&Coord{x: 2, y: 3}, Tile::new());
+		g.add(&Coord{x: 5, y: 0}, Tile::new());
+		display.visit_layer(&g, |_, _| drawn += 1);
+		display.visit_bg(Some(&g), |_| bg += 1);
+		assert_eq!((drawn, bg), (2, 24));
 	}
 
-	pub fn remove(&mut self, coord: &Coord) {
-		self.tile_array[self.coord_to_index(coord)] = None
+	#[test]
+	pub fn test_preview_geometry() {
+		let display = Display::new(1280.0, 720.0, 10, 6);
+		let tile = Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East);
+
+		let g = display.tile_geometry(&Coord{x: 9, y: 5}, &tile).expect("in bounds");
+		assert_eq!(g.body, display.tile_rect(&Coord{x: 9, y: 5}));
+		assert_eq!(g.edges, vec![(display.edge_rect(&Coord{x: 9, y: 5}, PlaneDir::East), BLACK)]);
+		// arrow points east
+		assert!(g.arrow[0].x > g.body.center().x);
+
+		assert!(display.tile_geometry(&Coord{x: 10, y: 0}, &tile).is_none());
+		assert!(display.tile_geometry(&Coord{x: 0, y: 6}, &tile).is_none());
 	}
 
-	pub fn add(&mut self, coord: &Coord, t: T) {
-		self.tile_array[self.coord_to_index(coord)] = Some(t)
+	#[test]
+	pub fn test_type_color() {
+		let display = Display::new(1280.0, 720.0, 10, 6);
+		let road = Tile::from_type(TileType::Road, PlaneDir::North);
+		let g = display.tile_geometry(&Coord{x: 0, y: 0}, &road).unwrap();
+		assert_eq!(g.color, DARKGRAY);
+		// colored by type, not by edges that look like a plain floor
+		let sidewalk = Tile::from_type(TileType::Sidewalk, PlaneDir::North);
+		assert!(sidewalk.same_edges(&road));
+		assert_eq!(display.tile_geometry(&Coord{x: 0, y: 0}, &sidewalk).unwrap().color, LIGHTGRAY);
 	}
-}
 
-struct TileMap {
-	layers: HashMap<Elevation, Grid<Tile>>,
-}
+	#[test]
+	pub fn test_agent_pos() {
+		let display = Display::new(1280.0, 720.0, 10, 6);
+		let mut agent = Agent::new(Elevation(0), Coord{x: 1, y: 1});
+		assert_eq!(display.agent_pos(&agent), display.tile_rect(&Coord{x: 1, y: 1}).center());
 
-#[cfg(test)]
-mod test {
-	use super::*;
-	use std::panic::catch_unwind;
+		agent.follow(vec![(Elevation(0), Coord{x: 2, y: 1})]);
+		agent.progress = 0.5;
+		let a = display.tile_rect(&Coord{x: 1, y: 1}).center();
+		let b = display.tile_rect(&Coord{x: 2, y: 1}).center();
+		assert_eq!(display.agent_pos(&agent), (a + b) / 2.0);
+	}
 
 	#[test]
-	pub fn test_grid() {
-		let g = Grid::<Tile>::new(3, 4);
-		let coords = vec![(1, 7), (3, 2), (8, 9)];
-		for c in &coords {
-			let coord = Coord::from(c);
-			let result = catch_unwind(|| {
-				g.get(&coord);
-			});
-			assert!(result.is_err());
+	pub fn test_key_bindings() {
+		let defaults = KeyBindings::default();
+		for action in Action::ALL {
+			assert!(!defaults.keys(action).is_empty(), "{:?} has no key", action);
 		}
+		let only = |key: KeyCode| move |k: KeyCode| k == key;
+		assert!(defaults.triggered(Action::Save, true, false, only(KeyCode::S)));
+		// ctrl and shift have to match
+		assert!(!defaults.triggered(Action::Save, false, false, only(KeyCode::S)));
+		assert!(!defaults.triggered(Action::Rotate, true, false, only(KeyCode::R)));
+		assert!(defaults.triggered(Action::NextLayer, false, false, only(KeyCode::RightBracket)));
+		assert!(defaults.triggered(Action::CopyToFile, true, true, only(KeyCode::C)));
+		assert!(!defaults.triggered(Action::Copy, true, true, only(KeyCode::C)));
+
+		let mut remapped = defaults.clone();
+		remapped.rebind(Action::Rotate, vec![KeyBinding::plain(KeyCode::T)]);
+		assert!(remapped.triggered(Action::Rotate, false, false, only(KeyCode::T)));
+		assert!(!remapped.triggered(Action::Rotate, false, false, only(KeyCode::R)));
+		let fired: Vec<Action> = Action::ALL.into_iter().filter(|a| remapped.triggered(*a, false, false, only(KeyCode::T))).collect();
+		assert_eq!(fired, vec![Action::Rotate]);
+		assert!(remapped.triggered(Action::Undo, true, false, only(KeyCode::Z)));
 	}
 
+	#[cfg(feature = "serde")]
 	#[test]
-	pub fn test_add_and_get() {
-		let mut g = Grid::<u8>::new(2, 3);
-		let coord = Coord{x: 1, y: 0};
-		let val = 7u8;
-		g.add(&coord, val);
-		match g.get(&coord) {
-			Some(i) => assert_eq!(*i, val),
-			None => panic!("expected {} at {:?}", val, coord),
-		};
+	pub fn test_loaded_key_overrides() {
+		let path = std::env::temp_dir().join(format!("walk-keys-{}.ron", std::process::id()));
+		std::fs::write(&path, r#"{Rotate: ["T", "shift+R"], Save: ["ctrl+W"], Undo: ["ctrl+Nope"]}"#).unwrap();
+		let bindings = initial_bindings(&path);
+		std::fs::remove_file(&path).unwrap();
+
+		let only = |key: KeyCode| move |k: KeyCode| k == key;
+		assert!(bindings.triggered(Action::Rotate, false, false, only(KeyCode::T)));
+		assert!(bindings.triggered(Action::Rotate, false, true, only(KeyCode::R)));
+		assert!(!bindings.triggered(Action::Rotate, false, false, only(KeyCode::R)));
+		assert!(bindings.triggered(Action::Save, true, false, only(KeyCode::W)));
+		assert!(!bindings.triggered(Action::Save, true, false, only(KeyCode::S)));
+		// a key that isn't known leaves the action as it was
+		assert!(bindings.triggered(Action::Undo, true, false, only(KeyCode::Z)));
+		// and so does having no file at all
+		assert_eq!(initial_bindings(&path), KeyBindings::default());
+
+		assert_eq!(KeyBinding::parse("ctrl+shift+C"), Some(KeyBinding::ctrl_shift(KeyCode::C)));
+		assert_eq!(KeyBinding::parse("alt+C"), None);
+		assert_eq!(KeyBinding::parse(""), None);
 	}
 
 	#[test]
-	pub fn test_rotate() {
-		assert_eq!(PlaneDir::North.clockwise(), PlaneDir::East);
-		assert_eq!(PlaneDir::East.clockwise(), PlaneDir::South);
-		assert_eq!(PlaneDir::South.clockwise(), PlaneDir::West);
-		assert_eq!(PlaneDir::West.clockwise(), PlaneDir::North);
-		assert_eq!(PlaneDir::North.anticlockwise(), PlaneDir::West);
-		assert_eq!(PlaneDir::East.anticlockwise(), PlaneDir::North);
-		assert_eq!(PlaneDir::South.anticlockwise(), PlaneDir::East);
-		assert_eq!(PlaneDir::West.anticlockwise(), PlaneDir::South);
-		assert_eq!(PlaneDir::North.rotate_diff(PlaneDir::South), 2);
-		assert_eq!(PlaneDir::East.rotate_diff(PlaneDir::North), -1);
-
-		let diff = PlaneDir::West.rotate_diff(PlaneDir::South);
-		assert_eq!(PlaneDir::West.rotate(diff), PlaneDir::South);
+	pub fn test_parse_args() {
+		let args = |v: &[&str]| v.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter();
+		assert_eq!(parse_args(args(&["poubelle"])), Ok(None));
+		assert_eq!(parse_args(args(&["poubelle", "map.ron"])), Ok(Some(PathBuf::from("map.ron"))));
+		assert!(parse_args(args(&["poubelle", "a.ron", "b.ron"])).is_err());
 	}
 }
 
+fn type_color(ty: TileType) -> Color {
+	match ty {
+		TileType::Freewalk => BEIGE,
+		TileType::Sidewalk => LIGHTGRAY,
+		TileType::Road => DARKGRAY,
+		TileType::Impasse => GRAY,
+		TileType::Stair => GOLD,
+		TileType::Ramp => SKYBLUE,
+	}
+}
+
+// shapes making up a drawn tile, in display coordinates
+#[derive(Clone, Debug, PartialEq)]
+struct TileGeometry {
+	body: Rect,
+	color: Color,
+	edges: Vec<(Rect, Color)>,
+	arrow: [Vec2; 3],
+}
+
 #[derive(Debug)]
 struct Display {
 	grid_rect: Rect,
@@ -212,8 +420,14 @@ struct Display {
 
 impl Display {
 	const TILE_MARGIN: f32 = 1.0;
+	const EDGE_THICKNESS: f32 = 3.0;
+	const PREVIEW_ALPHA: f32 = 0.5;
+	// light gray, what every tile looks like with nothing on it
+	const EMPTY_TILE_COLOR: Color = Color{r: 220.0 / 255.0, g: 220.0 / 255.0, b: 220.0 / 255.0, a: 1.0};
 
 	pub fn new(swidth: f32, sheight: f32, x_tiles: usize, y_tiles: usize) -> Display {
+		// tile size comes from dividing by x_tiles
+		assert!(x_tiles > 0 && y_tiles > 0, "can't lay out a {}x{} grid of tiles", x_tiles, y_tiles);
 		let margin_fraction = 0.025;
 		let margin = swidth * margin_fraction;
 		let usable_fraction = 1.0 - (2.0 * margin_fraction);
@@ -233,23 +447,172 @@ impl Display {
 			self.grid_rect.y + ((coord.y as f32) * self.tile_side_len) + Self::TILE_MARGIN,
 			self.tile_side_len - (Self::TILE_MARGIN * 2.0),
 			self.tile_side_len - (Self::TILE_MARGIN * 2.0),
-			Color{r: 220.0, g: 220.0, b: 220.0, a: 1.0});
+			Self::EMPTY_TILE_COLOR);
 	}
 
-	pub fn draw_bg(&self) {
+	// repaints just the given cells of a layer drawn earlier, over the
+	// background as draw_bg left it and the tinted layer below if there was one
+	pub fn draw_dirty(&self, grid: &Grid<Tile>, below: Option<(&Grid<Tile>, Color)>, dirty: &HashSet<Coord>) {
+		for c in dirty.iter().filter(|c| grid.contains(c)) {
+			self.draw_empty_tile(c);
+			if let Some((under, tint)) = below && let Some(tile) = under.get(c) {
+				self.draw_tinted_tile(c, tile, tint);
+			}
+			if let Some(tile) = grid.get(c) {
+				self.draw_tile(c, tile);
+			}
+		}
+	}
+
+	// the grid's backing, and an empty tile in every cell under a layer with
+	// anything on it. an empty layer only gets the backing
+	pub fn draw_bg(&self, layer: Option<&Grid<Tile>>) {
         draw_rectangle(
 			self.grid_rect.x,
 			self.grid_rect.y,
 			self.grid_rect.w,
 			self.grid_rect.h,
 			DARKGRAY);
+		self.visit_bg(layer, |coord| self.draw_empty_tile(coord));
+	}
+
+	// calls draw for each cell draw_bg fills in under layer
+	fn visit_bg(&self, layer: Option<&Grid<Tile>>, mut draw: impl FnMut(&Coord)) {
+		if layer.is_none_or(|g| g.is_empty()) {
+			return;
+		}
 		for x in 0..self.grid_size.0 {
 			for y in 0..self.grid_size.1 {
-				self.draw_empty_tile(&Coord{x: x, y: y});
+				draw(&Coord{x: x, y: y});
 			}
 		}
 	}
 
+	pub fn tile_rect(&self, coord: &Coord) -> Rect {
+		Rect{
+			x: self.grid_rect.x + ((coord.x as f32) * self.tile_side_len) + Self::TILE_MARGIN,
+			y: self.grid_rect.y + ((coord.y as f32) * self.tile_side_len) + Self::TILE_MARGIN,
+			w: self.tile_side_len - (Self::TILE_MARGIN * 2.0),
+			h: self.tile_side_len - (Self::TILE_MARGIN * 2.0),
+		}
+	}
+
+	// from the top left of min's tile to the bottom right of max's
+	pub fn span_rect(&self, min: &Coord, max: &Coord) -> Rect {
+		self.tile_rect(min).combine_with(self.tile_rect(max))
+	}
+
+	// the strip of a tile's rect along one of its edges
+	fn edge_rect(&self, coord: &Coord, dir: PlaneDir) -> Rect {
+		let r = self.tile_rect(coord);
+		let t = Self::EDGE_THICKNESS;
+		match dir {
+			PlaneDir::North => Rect{x: r.x, y: r.y, w: r.w, h: t},
+			PlaneDir::East => Rect{x: r.x + r.w - t, y: r.y, w: t, h: r.h},
+			PlaneDir::South => Rect{x: r.x, y: r.y + r.h - t, w: r.w, h: t},
+			PlaneDir::West => Rect{x: r.x, y: r.y, w: t, h: r.h},
+		}
+	}
+
+	// None for coords outside the grid
+	pub fn tile_geometry(&self, coord: &Coord, tile: &Tile) -> Option<TileGeometry> {
+		if coord.x >= self.grid_size.0 || coord.y >= self.grid_size.1 {
+			return None;
+		}
+		let body = self.tile_rect(coord);
+		let color = type_color(tile.ty());
+		let mut edges = Vec::new();
+		for (dir, info) in tile.edges() {
+			if info.is_wall() {
+				edges.push((self.edge_rect(coord, dir), BLACK));
+			} else if info.is_step() {
+				edges.push((self.edge_rect(coord, dir), ORANGE));
+			}
+		}
+
+		// triangle from the middle of the tile pointing out of the facing edge
+		let (dx, dy) = tile.facing().offset();
+		let forward = Vec2::new(dx as f32, dy as f32);
+		let side = forward.perp();
+		let centre = body.center();
+		let reach = body.w * 0.35;
+		let arrow = [
+			centre + (forward * reach),
+			centre - (forward * reach * 0.5) + (side * reach * 0.6),
+			centre - (forward * reach * 0.5) - (side * reach * 0.6),
+		];
+		return Some(TileGeometry{body: body, color: color, edges: edges, arrow: arrow});
+	}
+
+	// every color multiplied by tint, channel by channel
+	fn draw_geometry(g: &TileGeometry, tint: Color) {
+		let fade = |c: Color| Color::new(c.r * tint.r, c.g * tint.g, c.b * tint.b, c.a * tint.a);
+		draw_rectangle(g.body.x, g.body.y, g.body.w, g.body.h, fade(g.color));
+		for (e, color) in &g.edges {
+			draw_rectangle(e.x, e.y, e.w, e.h, fade(*color));
+		}
+		draw_triangle(g.arrow[0], g.arrow[1], g.arrow[2], fade(DARKBROWN));
+	}
+
+	pub fn draw_tile(&self, coord: &Coord, tile: &Tile) {
+		if let Some(g) = self.tile_geometry(coord, tile) {
+			Self::draw_geometry(&g, WHITE);
+		}
+	}
+
+	// a tile from a layer other than the one being edited
+	pub fn draw_tinted_tile(&self, coord: &Coord, tile: &Tile, tint: Color) {
+		if let Some(g) = self.tile_geometry(coord, tile) {
+			Self::draw_geometry(&g, tint);
+		}
+	}
+
+	// what placing tile at coord would look like
+	pub fn draw_preview(&self, coord: &Coord, tile: &Tile) {
+		if let Some(g) = self.tile_geometry(coord, tile) {
+			Self::draw_geometry(&g, Color{a: Self::PREVIEW_ALPHA, ..WHITE});
+		}
+	}
+
+	pub fn draw_layer(&self, grid: &Grid<Tile>) {
+		self.visit_layer(grid, |coord, tile| self.draw_tile(coord, tile));
+	}
+
+	pub fn draw_tinted_layer(&self, grid: &Grid<Tile>, tint: Color) {
+		self.visit_layer(grid, |coord, tile| self.draw_tinted_tile(coord, tile, tint));
+	}
+
+	// calls draw for each tile of the layer. an empty layer is all
+	// background, so its cells aren't walked
+	fn visit_layer(&self, grid: &Grid<Tile>, mut draw: impl FnMut(&Coord, &Tile)) {
+		if grid.is_empty() {
+			return;
+		}
+		for (coord, tile) in grid.iter() {
+			draw(&coord, tile);
+		}
+	}
+
+	// where to draw an agent, part way between its tile and the next
+	pub fn agent_pos(&self, agent: &Agent) -> Vec2 {
+		let here = self.tile_rect(&agent.pos).center();
+		match agent.next() {
+			Some((_, next)) => here.lerp(self.tile_rect(next).center(), agent.progress),
+			None => here,
+		}
+	}
+
+	pub fn draw_agent(&self, agent: &Agent) {
+		let p = self.agent_pos(agent);
+		draw_circle(p.x, p.y, self.tile_side_len * 0.3, RED);
+	}
+
+	pub fn draw_selection(&self, region: &editor::Region) {
+		let a = self.tile_rect(&region.min);
+		let b = self.tile_rect(&region.max);
+		draw_rectangle_lines(a.x, a.y, (b.x + b.w) - a.x, (b.y + b.h) - a.y, 2.0, BLUE);
+	}
+
 	pub fn get_tile_coord_from_pos(&self, pos: (f32, f32)) -> Option<Coord> {
 		if !self.grid_rect.contains(Vec2::new(pos.0, pos.1)) {
 			return None;
@@ -258,19 +621,410 @@ impl Display {
 		let x = ((pos.0 - self.grid_rect.x) / self.tile_side_len).floor() as usize;
 		let y = ((pos.1 - self.grid_rect.y) / self.tile_side_len).floor() as usize;
 
+		// the rect's far edges and any space below the last row aren't a tile
+		if x >= self.grid_size.0 || y >= self.grid_size.1 {
+			return None;
+		}
 		return Some(Coord{x: x, y: y})
 	}
 }
 
-fn window_conf() -> Conf {
-	// try workarounds from https://github.com/not-fl3/macroquad/issues/924
-	Conf{
-		window_title: "pourquoi".to_owned(),
-		platform: Platform{
+// maps the grid render target, in display coordinates, onto the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct GridCamera {
+	// display point drawn at the top left of the screen
+	target: Vec2,
+	zoom: f32,
+}
+
+impl GridCamera {
+	pub fn new() -> GridCamera {
+		GridCamera{target: Vec2::ZERO, zoom: 1.0}
+	}
+
+	// as saved in a session, which may have been edited by hand
+	pub fn restored(target: (f32, f32), zoom: f32) -> GridCamera {
+		let target = Vec2::new(target.0, target.1);
+		if !target.is_finite() || !zoom.is_finite() || zoom <= 0.0 {
+			return GridCamera::new();
+		}
+		return GridCamera{target: target, zoom: zoom};
+	}
+
+	pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+		(world - self.target) * self.zoom
+	}
+
+	// zoomed so bounds fills as much of a viewport sized screen as it can
+	// without cropping, and centred in it
+	pub fn fit_to(bounds: Rect, viewport: Vec2) -> GridCamera {
+		if bounds.w <= 0.0 || bounds.h <= 0.0 {
+			return GridCamera{target: bounds.center() - (viewport / 2.0), zoom: 1.0};
+		}
+		let zoom = (viewport.x / bounds.w).min(viewport.y / bounds.h);
+		return GridCamera{target: bounds.center() - (viewport / zoom / 2.0), zoom: zoom};
+	}
+
+	pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+		(screen / self.zoom) + self.target
+	}
+
+	pub fn screen_to_tile(&self, display: &Display, screen: (f32, f32)) -> Option<Coord> {
+		let world = self.screen_to_world(Vec2::new(screen.0, screen.1));
+		display.get_tile_coord_from_pos((world.x, world.y))
+	}
+
+	pub fn world_rect_to_screen(&self, world: Rect) -> Rect {
+		let origin = self.world_to_screen(world.point());
+		let size = world.size() * self.zoom;
+		Rect::new(origin.x, origin.y, size.x, size.y)
+	}
+
+	// the tile under screen and where its hover highlight ends up on screen
+	pub fn hovered_tile(&self, display: &Display, screen: (f32, f32)) -> Option<(Coord, Rect)> {
+		let c = self.screen_to_tile(display, screen)?;
+		return Some((c, self.world_rect_to_screen(display.tile_rect(&c))));
+	}
+}
+
+// where a render target lands on screen when drawn out. drawing into a target
+// through a from_display_rect camera stores it bottom row first, so it has to
+// go out with flip_y for display y 0, and Coord.y 0, to be the top row again
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Blit {
+	dest: Rect,
+	flip_y: bool,
+}
+
+impl Blit {
+	pub fn new(camera: &GridCamera, size: Vec2) -> Blit {
+		let origin = camera.world_to_screen(Vec2::ZERO);
+		let dest = size * camera.zoom;
+		Blit{dest: Rect::new(origin.x, origin.y, dest.x, dest.y), flip_y: true}
+	}
+
+	pub fn draw(&self, texture: &Texture2D) {
+		draw_texture_ex(
+			texture,
+			self.dest.x,
+			self.dest.y,
+			WHITE,
+			DrawTextureParams{
+				dest_size: Some(self.dest.size()),
+				flip_y: self.flip_y,
+				..Default::default()
+			},
+		);
+	}
+}
+
+// a key and whether ctrl and shift have to be down with it. they have to
+// match exactly, so binding both r and ctrl r to different things works
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct KeyBinding {
+	key: KeyCode,
+	ctrl: bool,
+	shift: bool,
+}
+
+impl KeyBinding {
+	pub fn plain(key: KeyCode) -> KeyBinding {
+		KeyBinding{key: key, ctrl: false, shift: false}
+	}
+
+	pub fn ctrl(key: KeyCode) -> KeyBinding {
+		KeyBinding{key: key, ctrl: true, shift: false}
+	}
+
+	pub fn ctrl_shift(key: KeyCode) -> KeyBinding {
+		KeyBinding{key: key, ctrl: true, shift: true}
+	}
+
+	// keys that can be named in a keys file
+	const NAMED: [KeyCode; 66] = [
+		KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+		KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+		KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+		KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+		KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+		KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+		KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+		KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+		KeyCode::Space, KeyCode::Escape, KeyCode::Enter, KeyCode::Tab, KeyCode::Backspace,
+		KeyCode::Insert, KeyCode::Delete, KeyCode::Home, KeyCode::End, KeyCode::PageUp,
+		KeyCode::PageDown, KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+		KeyCode::LeftBracket, KeyCode::RightBracket, KeyCode::Minus,
+	];
+
+	// a key named as in KeyCode with any of "ctrl+" and "shift+" in front,
+	// e.g. "ctrl+shift+C"
+	pub fn parse(name: &str) -> Option<KeyBinding> {
+		let mut parts: Vec<&str> = name.split('+').map(str::trim).collect();
+		let key = parts.pop()?;
+		let key = Self::NAMED.into_iter().find(|k| format!("{:?}", k) == key)?;
+		let mut binding = KeyBinding::plain(key);
+		for m in parts {
+			match m.to_lowercase().as_str() {
+				"ctrl" => binding.ctrl = true,
+				"shift" => binding.shift = true,
+				_ => return None,
+			}
+		}
+		return Some(binding);
+	}
+}
+
+// which keys do what, an action can have any number of them
+#[derive(Clone, Debug, PartialEq)]
+struct KeyBindings {
+	keys: HashMap<Action, Vec<KeyBinding>>,
+}
+
+impl Default for KeyBindings {
+	fn default() -> KeyBindings {
+		use KeyBinding as K;
+		let keys = HashMap::from([
+			(Action::Cancel, vec![K::plain(KeyCode::Escape)]),
+			(Action::Rotate, vec![K::plain(KeyCode::R)]),
+			(Action::NextLayer, vec![K::plain(KeyCode::PageUp), K::plain(KeyCode::RightBracket)]),
+			(Action::PrevLayer, vec![K::plain(KeyCode::PageDown), K::plain(KeyCode::LeftBracket)]),
+			(Action::FillSelection, vec![K::plain(KeyCode::F)]),
+			(Action::ClearSelection, vec![K::plain(KeyCode::Delete)]),
+			(Action::Undo, vec![K::ctrl(KeyCode::Z)]),
+			(Action::Redo, vec![K::ctrl(KeyCode::Y)]),
+			(Action::Copy, vec![K::ctrl(KeyCode::C)]),
+			(Action::CopyToFile, vec![K::ctrl_shift(KeyCode::C)]),
+			(Action::Paste, vec![K::ctrl(KeyCode::V)]),
+			(Action::PasteFromFile, vec![K::ctrl_shift(KeyCode::V)]),
+			(Action::CheckMap, vec![K::plain(KeyCode::L)]),
+			(Action::Save, vec![K::ctrl(KeyCode::S)]),
+			(Action::FitView, vec![K::plain(KeyCode::Home)]),
+		]);
+		return KeyBindings{keys: keys};
+	}
+}
+
+impl KeyBindings {
+	pub fn keys(&self, action: Action) -> &[KeyBinding] {
+		self.keys.get(&action).map_or(&[], |k| k.as_slice())
+	}
+
+	// replaces whatever action was bound to
+	pub fn rebind(&mut self, action: Action, keys: Vec<KeyBinding>) {
+		self.keys.insert(action, keys);
+	}
+
+	// rebinds each action in overrides to the keys named for it, giving back
+	// the names that couldn't be read. an action with one of those keeps the
+	// keys it had, rather than losing some of them
+	pub fn apply(&mut self, overrides: &HashMap<Action, Vec<String>>) -> Vec<String> {
+		let mut bad = Vec::new();
+		for (action, names) in overrides {
+			let keys: Vec<Option<KeyBinding>> = names.iter().map(|n| KeyBinding::parse(n)).collect();
+			if keys.iter().all(|k| k.is_some()) {
+				self.rebind(*action, keys.into_iter().flatten().collect());
+			} else {
+				bad.extend(names.iter().zip(&keys).filter(|(_, k)| k.is_none()).map(|(n, _)| n.clone()));
+			}
+		}
+		return bad;
+	}
+
+	// whether a key bound to action was pressed, pressed says which keys were
+	pub fn triggered(&self, action: Action, ctrl: bool, shift: bool, pressed: impl Fn(KeyCode) -> bool) -> bool {
+		self.keys(action).iter().any(|k| k.ctrl == ctrl && k.shift == shift && pressed(k.key))
+	}
+
+	// triggered for this frame's keyboard
+	pub fn pressed(&self, action: Action) -> bool {
+		self.triggered(action, ctrl_down(), shift_down(), is_key_pressed)
+	}
+}
+
+fn ctrl_down() -> bool {
+	is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl)
+}
+
+fn shift_down() -> bool {
+	is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift)
+}
+
+// how the window is opened, read before anything else so it can only come
+// from the environment
+#[derive(Clone, Debug, PartialEq)]
+struct AppConfig {
+	title: String,
+	width: i32,
+	height: i32,
+	webgl_version: WebGLVersion,
+}
+
+impl Default for AppConfig {
+	fn default() -> AppConfig {
+		let conf = Conf::default();
+		AppConfig{
+			title: "pourquoi".to_owned(),
+			width: conf.window_width,
+			height: conf.window_height,
+			// try workarounds from https://github.com/not-fl3/macroquad/issues/924
 			webgl_version: WebGLVersion::WebGL2,
+		}
+	}
+}
+
+impl AppConfig {
+	// POUBELLE_TITLE, POUBELLE_WIDTH, POUBELLE_HEIGHT and POUBELLE_WEBGL (1 or 2)
+	pub fn from_env() -> AppConfig {
+		Self::from_vars(|name| std::env::var(name).ok())
+	}
+
+	// anything unset or that doesn't parse keeps its default
+	pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> AppConfig {
+		let mut config = AppConfig::default();
+		if let Some(title) = var("POUBELLE_TITLE") {
+			config.title = title;
+		}
+		let size = |name| var(name).and_then(|v| v.trim().parse::<i32>().ok()).filter(|n| *n > 0);
+		if let Some(w) = size("POUBELLE_WIDTH") {
+			config.width = w;
+		}
+		if let Some(h) = size("POUBELLE_HEIGHT") {
+			config.height = h;
+		}
+		match var("POUBELLE_WEBGL").as_deref().map(str::trim) {
+			Some("1") => config.webgl_version = WebGLVersion::WebGL1,
+			Some("2") => config.webgl_version = WebGLVersion::WebGL2,
+			_ => {},
+		}
+		return config;
+	}
+
+	pub fn conf(&self) -> Conf {
+		Conf{
+			window_title: self.title.clone(),
+			window_width: self.width,
+			window_height: self.height,
+			platform: Platform{
+				webgl_version: self.webgl_version,
+				..Default::default()
+			},
 			..Default::default()
-		},
-		..Default::default()
+		}
+	}
+}
+
+// the largest rect with the target's aspect that fits the window, centred
+// so whatever's left over is split evenly into bars
+fn letterbox_rect(window_w: f32, window_h: f32, target_w: f32, target_h: f32) -> Rect {
+	let scale = (window_w / target_w).min(window_h / target_h);
+	let (w, h) = (target_w * scale, target_h * scale);
+	Rect::new((window_w - w) / 2.0, (window_h - h) / 2.0, w, h)
+}
+
+// a window position as a position on a target drawn letterboxed into frame
+fn window_to_target(frame: &Rect, target: Vec2, pos: (f32, f32)) -> (f32, f32) {
+	let p = (Vec2::new(pos.0, pos.1) - frame.point()) * (target / frame.size());
+	(p.x, p.y)
+}
+
+fn window_conf() -> Conf {
+	AppConfig::from_env().conf()
+}
+
+// this frame's events from the window, mp is the mouse in render target
+// coordinates
+fn poll_input(input: &mut InputState, bindings: &KeyBindings, hovered: Option<Coord>, mp: (f32, f32)) -> Vec<InputEvent> {
+	let pressed = if is_mouse_button_pressed(MouseButton::Left) {
+		Some(Button::Left)
+	} else if is_mouse_button_pressed(MouseButton::Right) {
+		Some(Button::Right)
+	} else {
+		None
+	};
+	let mut events = input.mouse(&MouseFrame{
+		pos: mp,
+		hovered: hovered,
+		pressed: pressed,
+		down: is_mouse_button_down(MouseButton::Left) || is_mouse_button_down(MouseButton::Right),
+		released: is_mouse_button_released(MouseButton::Left) || is_mouse_button_released(MouseButton::Right),
+		shift: shift_down(),
+		alt: is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt),
+	});
+	for action in Action::ALL {
+		if bindings.pressed(action) {
+			events.push(InputEvent::Key{action: action});
+		}
+	}
+	let template_keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5];
+	for (key, template) in template_keys.iter().zip(TileDirTemplate::ALL) {
+		if is_key_pressed(*key) {
+			events.push(InputEvent::SelectTemplate{template: template});
+		}
+	}
+	return events;
+}
+
+// hands each event to the editor, doing the parts it leaves to us on either
+// side of it
+#[allow(clippy::too_many_arguments)]
+fn handle_input(
+	editor: &mut Editor,
+	map: &mut TileMap,
+	agents: &mut Vec<Agent>,
+	display: &Display,
+	camera: &mut GridCamera,
+	save_path: &Path,
+	screen_size: Vec2,
+	events: &[InputEvent],
+) {
+	for event in events {
+		// the file has to be loaded before the editor pastes from it
+		if matches!(event, InputEvent::Key{action: Action::PasteFromFile}) {
+			match mapfile::load_fragment(Path::new(CLIPBOARD_PATH)) {
+				Ok(fragment) => editor.clipboard = Some(fragment),
+				Err(e) => error!("couldn't load {}: {}", CLIPBOARD_PATH, e),
+			}
+		}
+		editor.handle(map, event);
+		match *event {
+			InputEvent::Spawn{coord} => {
+				let here = (editor.active_elevation, coord);
+				match editor.spawn_point.and_then(|start| agent::spawn(map, start, here)) {
+					Some(a) => agents.push(a),
+					None => info!("no route to {:?}", here),
+				}
+			},
+			InputEvent::Key{action: Action::CopyToFile} => {
+				if let Some(fragment) = &editor.clipboard {
+					match mapfile::save_fragment(fragment, Path::new(CLIPBOARD_PATH)) {
+						Ok(()) => info!("saved clipboard to {}", CLIPBOARD_PATH),
+						Err(e) => error!("couldn't save {}: {}", CLIPBOARD_PATH, e),
+					}
+				}
+			},
+			// warnings from the check go to the log
+			InputEvent::Key{action: Action::CheckMap} => {
+				let warnings = lint::lint(map);
+				for w in &warnings {
+					warn!("{}", w);
+				}
+				info!("map check found {} problems", warnings.len());
+			},
+			InputEvent::Key{action: Action::Save} => {
+				match mapfile::save_map(map, save_path) {
+					Ok(()) => info!("saved map to {:?}", save_path),
+					Err(e) => error!("couldn't save {:?}: {}", save_path, e),
+				}
+			},
+			// frames whatever is on the map, or all of it if there's nothing yet
+			InputEvent::Key{action: Action::FitView} => {
+				let (x_tiles, y_tiles) = display.grid_size;
+				let full = (Coord{x: 0, y: 0}, Coord{x: x_tiles - 1, y: y_tiles - 1});
+				let (min, max) = map.occupied_bounds().unwrap_or(full);
+				*camera = GridCamera::fit_to(display.span_rect(&min, &max), screen_size);
+			},
+			_ => (),
+		}
 	}
 }
 
@@ -278,63 +1032,131 @@ fn window_conf() -> Conf {
 async fn main() {
 	info!("logging check");
 
-	let x_tiles = 48;
-	let y_tiles = 32;
-	//let mut g = Grid::<Tile>::new(x_tiles, y_tiles);
+	let map_path = match parse_args(std::env::args()) {
+		Ok(p) => p,
+		Err(e) => {
+			error!("{}", e);
+			None
+		},
+	};
+	let mut map = initial_map(map_path.as_deref());
+	let save_path = map_path.unwrap_or_else(|| PathBuf::from("map.ron"));
+	let (x_tiles, y_tiles) = map.footprint();
+	let mut editor = Editor::new();
+	let session = initial_session(Path::new(SESSION_PATH));
+	editor.restore(&session);
+	let mut agents: Vec<Agent> = Vec::with_capacity(AGENT_CAPACITY);
+	let bindings = initial_bindings(Path::new(KEYS_PATH));
+	let mut input = InputState::new();
 
 	let swidth = 1280;
 	let sheight = 720;
+	let screen_size = Vec2::new(swidth as f32, sheight as f32);
 	let grid_rt = render_target(swidth, sheight);
 	//grid_rt.texture.set_filter(FilterMode::Linear);
 
 	let mut grid_camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, swidth as f32, sheight as f32));
 	grid_camera.render_target = Some(grid_rt);
+	// the layer's tiles are kept drawn here and only repainted where they change
+	let tiles_rt = render_target(swidth, sheight);
+	let mut tiles_camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, swidth as f32, sheight as f32));
+	tiles_camera.render_target = Some(tiles_rt.clone());
+	// draws the finished frame into the window, its viewport follows the letterbox
+	let mut window_camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, screen_size.x, screen_size.y));
+	// the layer on tiles_rt and whether it was empty, which left the cells
+	// out of the background
+	let mut drawn_layer = None;
+	let mut camera = GridCamera::restored(session.camera_target, session.camera_zoom);
+	// closing the window comes through the loop, so the session can be saved
+	prevent_quit();
+	// the map never changes size, so neither does the layout
+	let display = Display::new(swidth as f32, sheight as f32, x_tiles, y_tiles);
+	let mut label = String::new();
 
     loop {
-		set_camera(&grid_camera);
-
-        clear_background(BLACK);
-		let display = Display::new(swidth as f32, sheight as f32, x_tiles, y_tiles);
-		//display.draw_bg();
-
-        draw_line(40.0, 40.0, 100.0, 200.0, 15.0, BLUE);
-        draw_circle(swidth as f32 - 30.0, sheight as f32 - 30.0, 15.0, YELLOW);
-        draw_text("IT WORKS!", 20.0, 20.0, 30.0, DARKGRAY);
+		let frame = letterbox_rect(screen_width(), screen_height(), screen_size.x, screen_size.y);
+		let mp = window_to_target(&frame, screen_size, mouse_position());
 
+		set_camera(&tiles_camera);
+		// the next layer down shows through faintly, in its own tint
+		let below = map.layers()
+			.take_while(|(e, _)| *e < editor.active_elevation)
+			.last()
+			.map(|(e, g)| (g, Color::from(map.layer_meta(e).tint)));
+		let empty = map.layer(editor.active_elevation).is_none_or(|g| g.is_empty());
+		if drawn_layer != Some((editor.active_elevation, empty)) {
+			clear_background(BLACK);
+			display.draw_bg(map.layer(editor.active_elevation));
+			if let Some((under, tint)) = below {
+				display.draw_tinted_layer(under, tint);
+			}
+			if let Some(layer) = map.layer(editor.active_elevation) {
+				display.draw_layer(layer);
+			}
+			drawn_layer = Some((editor.active_elevation, empty));
+		} else if let Some(layer) = map.layer(editor.active_elevation) {
+			display.draw_dirty(layer, below, editor.history.dirty());
+		}
+		editor.history.clear_dirty();
 
-		let cam = Camera2D{
-			offset: Vec2::new(0.1, 0.2),
-			zoom: Vec2::ONE,
-			target: Vec2::ZERO,
-			..Default::default()
-		};
+		set_camera(&grid_camera);
+        clear_background(BLACK);
+		Blit::new(&GridCamera::new(), screen_size).draw(&tiles_rt.texture);
+		if let editor::Operation::Paint(cells) = &editor.operation {
+			let tile = editor.current_tile();
+			for c in cells {
+				display.draw_tile(c, &tile);
+			}
+		}
+		if let Some(region) = &editor.shown_selection() {
+			display.draw_selection(region);
+		}
+		agents.retain(|a| !a.is_idle());
+		for agent in &mut agents {
+			agent.update(&map, get_frame_time(), AGENT_SPEED);
+			let on_layer = agent.elevation == editor.active_elevation ||
+				agent.next().is_some_and(|(e, _)| *e == editor.active_elevation);
+			if on_layer {
+				display.draw_agent(agent);
+			}
+		}
+		let hover = camera.hovered_tile(&display, mp);
+		let hovered = hover.map(|(c, _)| c);
+		if editor.operation == editor::Operation::Idle && let Some(c) = hovered {
+			display.draw_preview(&c, &editor.current_tile());
+		}
+		label.clear();
+		write!(label, "elevation {}", editor.active_elevation).unwrap();
+		draw_text(&label, 4.0, 24.0, 24.0, WHITE);
 
-		set_camera(&cam);
-		//set_default_camera();
-        //clear_background(BLACK);
-		draw_texture_ex(
-			&grid_camera.render_target.as_ref().unwrap().texture,
-			0.0,
-			0.0,
-			WHITE,  // pourquoi?
-			DrawTextureParams{
-				dest_size: Some(Vec2::ONE),
-				flip_y: true,
-				..Default::default()
-			},
-		);
+		// out to the window, scaled into frame with bars round it rather than
+		// stretched, which is why mp went through window_to_target
+		set_default_camera();
+		clear_background(BLACK);
+		window_camera.viewport = Some((frame.x as i32, frame.y as i32, frame.w as i32, frame.h as i32));
+		set_camera(&window_camera);
+		Blit::new(&camera, screen_size).draw(&grid_camera.render_target.as_ref().unwrap().texture);
+		// outlined on screen rather than in the target so it keeps the same
+		// thickness at any zoom
+		if let Some((_, r)) = hover {
+			draw_rectangle_lines(r.x, r.y, r.w, r.h, 2.0, YELLOW);
+		}
 
 		if is_mouse_button_pressed(MouseButton::Left) {
-			let mp = mouse_position();
-			info!("mouse position {:?} {:?} {} {}",
-					mp,
-					cam,
-					cam.world_to_screen(Vec2::new(mp.0, mp.1)),
-					cam.screen_to_world(Vec2::new(mp.0, mp.1)));
-			if let Some(grid_coord) = display.get_tile_coord_from_pos(mp) {
+			info!("mouse position {:?} world {}", mp, camera.screen_to_world(Vec2::new(mp.0, mp.1)));
+			if let Some(grid_coord) = camera.screen_to_tile(&display, mp) {
 				info!("clicked on tile at {:?}", grid_coord);
 			}
 		}
+		let events = poll_input(&mut input, &bindings, hovered, mp);
+		handle_input(&mut editor, &mut map, &mut agents, &display, &mut camera, &save_path, screen_size, &events);
+		if is_quit_requested() {
+			let state = editor.state((camera.target.x, camera.target.y), camera.zoom);
+			if let Err(e) = mapfile::save_session(&state, Path::new(SESSION_PATH)) {
+				error!("couldn't save {}: {}", SESSION_PATH, e);
+			}
+			break;
+		}
 
         next_frame().await
     }
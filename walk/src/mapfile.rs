@@ -0,0 +1,715 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+#[cfg(feature = "serde")]
+use std::io::Read;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::path::Path;
+
+use crate::{Coord, Elevation, Tile, TileMap, TileType};
+use crate::editor::{EditorState, MapFragment};
+use crate::input::Action;
+#[cfg(feature = "serde")]
+use crate::{Grid, LayerMeta, TileDirTemplate};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// bumped whenever the saved layout changes, see migrate
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum MapParseError {
+	Io(io::Error),
+	#[cfg(feature = "serde")]
+	Ron(ron::error::SpannedError),
+	#[cfg(feature = "serde")]
+	Json(serde_json::Error),
+	UnsupportedVersion{found: u32, expected: u32},
+	// layer whose size doesn't match the map's footprint
+	BadLayer(Elevation),
+	// ascii maps, lines and columns count from 1
+	Empty,
+	Ragged{line: usize, expected: usize, found: usize},
+	UnknownChar{line: usize, column: usize, ch: char},
+	UnknownName{line: usize, column: usize, name: String},
+}
+
+impl fmt::Display for MapParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(e) => write!(f, "reading map: {}", e),
+			#[cfg(feature = "serde")]
+			Self::Ron(e) => write!(f, "parsing map: {}", e),
+			#[cfg(feature = "serde")]
+			Self::Json(e) => write!(f, "parsing map: {}", e),
+			Self::UnsupportedVersion{found, expected} =>
+				write!(f, "map format {} can't be read, expected {} or older", found, expected),
+			Self::BadLayer(elevation) => write!(f, "layer {} doesn't match the map size", elevation),
+			Self::Empty => write!(f, "map has no rows"),
+			Self::Ragged{line, expected, found} =>
+				write!(f, "line {} is {} wide, expected {}", line, found, expected),
+			Self::UnknownChar{line, column, ch} =>
+				write!(f, "unknown tile {:?} at line {} column {}", ch, line, column),
+			Self::UnknownName{line, column, name} =>
+				write!(f, "unknown tile {:?} at line {} column {}", name, line, column),
+		}
+	}
+}
+
+impl std::error::Error for MapParseError {}
+
+impl From<io::Error> for MapParseError {
+	fn from(e: io::Error) -> Self {
+		Self::Io(e)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl From<ron::error::SpannedError> for MapParseError {
+	fn from(e: ron::error::SpannedError) -> Self {
+		Self::Ron(e)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for MapParseError {
+	fn from(e: serde_json::Error) -> Self {
+		Self::Json(e)
+	}
+}
+
+#[derive(Debug)]
+pub enum MapImportError {
+	Image(image::ImageError),
+	// a pixel whose color isn't in the palette
+	UnknownColor{x: usize, y: usize, color: [u8; 3]},
+}
+
+impl fmt::Display for MapImportError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Image(e) => write!(f, "reading image: {}", e),
+			Self::UnknownColor{x, y, color: [r, g, b]} =>
+				write!(f, "pixel ({}, {}) has color #{:02x}{:02x}{:02x}, not in the palette", x, y, r, g, b),
+		}
+	}
+}
+
+impl std::error::Error for MapImportError {}
+
+impl From<image::ImageError> for MapImportError {
+	fn from(e: image::ImageError) -> Self {
+		Self::Image(e)
+	}
+}
+
+// the map as written to ron and json, tagged with the format it's in
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+struct SavedMap<'a> {
+	format: u32,
+	layers: &'a BTreeMap<Elevation, Grid<Tile>>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	layer_meta: &'a BTreeMap<Elevation, LayerMeta>,
+	width: usize,
+	height: usize,
+}
+
+// files from before the tag read as format 0
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct LoadedMap {
+	#[serde(default)]
+	format: u32,
+	layers: BTreeMap<Elevation, Grid<Tile>>,
+	// left out when no layer has any
+	#[serde(default)]
+	layer_meta: BTreeMap<Elevation, LayerMeta>,
+	width: usize,
+	height: usize,
+}
+
+#[cfg(feature = "serde")]
+impl LoadedMap {
+	// brings an older file up to the next format
+	fn migrate(&mut self) {
+		match self.format {
+			// tiles didn't carry a type, so everything came back as the default.
+			// take it from the edges instead
+			0 => {
+				for tile in self.layers.values_mut().flat_map(|g| g.tile_array.iter_mut().flatten()) {
+					if tile.ty == TileType::default() && let Some(template) = TileDirTemplate::infer(tile) {
+						tile.ty = template.default_type();
+					}
+				}
+			},
+			_ => unreachable!("no migration from format {}", self.format),
+		}
+		self.format += 1;
+	}
+
+	fn into_map(mut self) -> Result<TileMap, MapParseError> {
+		if self.format > FORMAT_VERSION {
+			return Err(MapParseError::UnsupportedVersion{found: self.format, expected: FORMAT_VERSION});
+		}
+		while self.format < FORMAT_VERSION {
+			self.migrate();
+		}
+		let mut map = TileMap::new(self.width, self.height);
+		map.layers = self.layers;
+		map.layer_meta = self.layer_meta;
+		map.validate()?;
+		return Ok(map);
+	}
+}
+
+impl TileMap {
+	#[cfg(feature = "serde")]
+	fn saved(&self) -> SavedMap<'_> {
+		SavedMap{format: FORMAT_VERSION, layers: &self.layers, layer_meta: &self.layer_meta, width: self.width, height: self.height}
+	}
+
+	// deserialized layers are trusted for indexing, so check them up front
+	#[cfg(feature = "serde")]
+	fn validate(&self) -> Result<(), MapParseError> {
+		for (elevation, g) in &self.layers {
+			// the sizes come straight from the file, so they can be too big to multiply
+			if g.width != self.width ||
+			   g.height != self.height ||
+			   self.width.checked_mul(self.height) != Some(g.tile_array.len()) {
+				return Err(MapParseError::BadLayer(*elevation));
+			}
+		}
+		return Ok(());
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn from_ron(text: &str) -> Result<TileMap, MapParseError> {
+		let file: LoadedMap = ron::from_str(text)?;
+		return file.into_map();
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn to_ron(&self) -> Result<String, ron::Error> {
+		ron::ser::to_string_pretty(&self.saved(), ron::ser::PrettyConfig::default())
+	}
+
+	// same schema as the ron files, elevations become string keys
+	#[cfg(feature = "serde")]
+	pub fn from_json(text: &str) -> Result<TileMap, MapParseError> {
+		let file: LoadedMap = serde_json::from_str(text)?;
+		return file.into_map();
+	}
+
+	#[cfg(feature = "serde")]
+	pub fn to_json(&self) -> Result<String, serde_json::Error> {
+		serde_json::to_string_pretty(&self.saved())
+	}
+}
+
+#[cfg(feature = "serde")]
+impl MapFragment {
+	pub fn from_ron(text: &str) -> Result<MapFragment, MapParseError> {
+		Ok(ron::from_str(text)?)
+	}
+
+	pub fn to_ron(&self) -> Result<String, ron::Error> {
+		ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+	}
+}
+
+#[cfg(feature = "serde")]
+impl EditorState {
+	pub fn from_ron(text: &str) -> Result<EditorState, MapParseError> {
+		Ok(ron::from_str(text)?)
+	}
+
+	pub fn to_ron(&self) -> Result<String, ron::Error> {
+		ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+	}
+}
+
+// one character per tile as in TileType::from_char, space for no tile.
+// every line has to be the same width. the result has a single layer at 0
+pub fn load_ascii(text: &str) -> Result<TileMap, MapParseError> {
+	let rows: Vec<Vec<char>> = text.lines().map(|l| l.chars().collect()).collect();
+	let width = rows.first().ok_or(MapParseError::Empty)?.len();
+	let mut map = TileMap::new(width, rows.len());
+	let layer = map.layer_mut(Elevation(0));
+	for (y, row) in rows.iter().enumerate() {
+		if row.len() != width {
+			return Err(MapParseError::Ragged{line: y + 1, expected: width, found: row.len()});
+		}
+		for (x, ch) in row.iter().enumerate() {
+			if *ch == ' ' {
+				continue;
+			}
+			let Some(ty) = TileType::from_char(*ch) else {
+				return Err(MapParseError::UnknownChar{line: y + 1, column: x + 1, ch: *ch});
+			};
+			layer.add(&Coord{x: x, y: y}, Tile::from_type(ty, Tile::DEFAULT_FACING));
+		}
+	}
+	return Ok(map);
+}
+
+// the layer at elevation in load_ascii's format, one line per row
+pub fn to_ascii(map: &TileMap, elevation: Elevation) -> String {
+	to_ascii_with(map, elevation, ' ')
+}
+
+// as to_ascii, drawing cells without a tile as empty
+pub fn to_ascii_with(map: &TileMap, elevation: Elevation, empty: char) -> String {
+	let (width, height) = map.footprint();
+	let mut out = String::with_capacity((width + 1) * height);
+	for y in 0..height {
+		for x in 0..width {
+			out.push(map.get(elevation, &Coord{x: x, y: y}).map_or(empty, |t| t.ty.to_char()));
+		}
+		out.push('\n');
+	}
+	return out;
+}
+
+// one row per line, each cell the TileType name or blank for no tile
+pub fn to_csv(map: &TileMap, elevation: Elevation) -> String {
+	let (width, height) = map.footprint();
+	let mut out = String::new();
+	for y in 0..height {
+		let row: Vec<&str> = (0..width)
+			.map(|x| map.get(elevation, &Coord{x: x, y: y}).map_or("", |t| t.ty.name()))
+			.collect();
+		out.push_str(&row.join(","));
+		out.push('\n');
+	}
+	return out;
+}
+
+// reads to_csv's format back into a map with a single layer at 0
+pub fn from_csv(text: &str) -> Result<TileMap, MapParseError> {
+	let rows: Vec<Vec<&str>> = text.lines().map(|l| l.split(',').map(str::trim).collect()).collect();
+	let width = rows.first().ok_or(MapParseError::Empty)?.len();
+	let mut map = TileMap::new(width, rows.len());
+	let layer = map.layer_mut(Elevation(0));
+	for (y, row) in rows.iter().enumerate() {
+		if row.len() != width {
+			return Err(MapParseError::Ragged{line: y + 1, expected: width, found: row.len()});
+		}
+		for (x, name) in row.iter().enumerate() {
+			if name.is_empty() {
+				continue;
+			}
+			let Some(ty) = TileType::from_name(name) else {
+				return Err(MapParseError::UnknownName{line: y + 1, column: x + 1, name: name.to_string()});
+			};
+			layer.add(&Coord{x: x, y: y}, Tile::from_type(ty, Tile::DEFAULT_FACING));
+		}
+	}
+	return Ok(map);
+}
+
+// one tile per pixel, colored as in the palette. the result has a single layer at 0
+pub fn load_image_map(path: &Path, palette: &HashMap<[u8; 3], TileType>) -> Result<TileMap, MapImportError> {
+	let img = image::open(path)?.to_rgb8();
+	return image_to_map(&img, palette);
+}
+
+pub fn image_to_map(img: &image::RgbImage, palette: &HashMap<[u8; 3], TileType>) -> Result<TileMap, MapImportError> {
+	let mut map = TileMap::new(img.width() as usize, img.height() as usize);
+	let layer = map.layer_mut(Elevation(0));
+	for (x, y, pixel) in img.enumerate_pixels() {
+		let (x, y) = (x as usize, y as usize);
+		let Some(ty) = palette.get(&pixel.0) else {
+			return Err(MapImportError::UnknownColor{x: x, y: y, color: pixel.0});
+		};
+		layer.add(&Coord{x: x, y: y}, Tile::from_type(*ty, Tile::DEFAULT_FACING));
+	}
+	return Ok(map);
+}
+
+// as TileMap::from_ron, without having the whole text in a String first.
+// ron still buffers the bytes internally, it can't parse as they arrive
+#[cfg(feature = "serde")]
+pub fn load_ron_reader<R: Read>(reader: R) -> Result<TileMap, MapParseError> {
+	let file: LoadedMap = ron::de::from_reader(reader)?;
+	return file.into_map();
+}
+
+#[cfg(feature = "serde")]
+pub fn load_map(path: &Path) -> Result<TileMap, MapParseError> {
+	let file = fs::File::open(path)?;
+	return load_ron_reader(io::BufReader::new(file));
+}
+
+#[cfg(feature = "serde")]
+pub fn save_map(map: &TileMap, path: &Path) -> io::Result<()> {
+	let text = map.to_ron().map_err(io::Error::other)?;
+	return fs::write(path, text);
+}
+
+#[cfg(feature = "serde")]
+pub fn load_fragment(path: &Path) -> Result<MapFragment, MapParseError> {
+	let text = fs::read_to_string(path)?;
+	return MapFragment::from_ron(&text);
+}
+
+#[cfg(feature = "serde")]
+pub fn save_fragment(fragment: &MapFragment, path: &Path) -> io::Result<()> {
+	let text = fragment.to_ron().map_err(io::Error::other)?;
+	return fs::write(path, text);
+}
+
+#[cfg(feature = "serde")]
+pub fn load_session(path: &Path) -> Result<EditorState, MapParseError> {
+	let text = fs::read_to_string(path)?;
+	return EditorState::from_ron(&text);
+}
+
+#[cfg(feature = "serde")]
+pub fn save_session(state: &EditorState, path: &Path) -> io::Result<()> {
+	let text = state.to_ron().map_err(io::Error::other)?;
+	return fs::write(path, text);
+}
+
+// keys to bind in place of the defaults, by action. the names are whatever
+// has the window to make sense of, e.g. "ctrl+shift+C"
+#[cfg(feature = "serde")]
+pub fn load_key_overrides(path: &Path) -> Result<HashMap<Action, Vec<String>>, MapParseError> {
+	let text = fs::read_to_string(path)?;
+	return Ok(ron::from_str(&text)?);
+}
+
+#[cfg(not(feature = "serde"))]
+fn unsupported() -> io::Error {
+	io::Error::new(io::ErrorKind::Unsupported, "built without the serde feature")
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn load_map(_path: &Path) -> Result<TileMap, MapParseError> {
+	return Err(MapParseError::Io(unsupported()));
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn save_map(_map: &TileMap, _path: &Path) -> io::Result<()> {
+	return Err(unsupported());
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn load_fragment(_path: &Path) -> Result<MapFragment, MapParseError> {
+	return Err(MapParseError::Io(unsupported()));
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn save_fragment(_fragment: &MapFragment, _path: &Path) -> io::Result<()> {
+	return Err(unsupported());
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn load_session(_path: &Path) -> Result<EditorState, MapParseError> {
+	return Err(MapParseError::Io(unsupported()));
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn save_session(_state: &EditorState, _path: &Path) -> io::Result<()> {
+	return Err(unsupported());
+}
+
+#[cfg(not(feature = "serde"))]
+pub fn load_key_overrides(_path: &Path) -> Result<HashMap<Action, Vec<String>>, MapParseError> {
+	return Err(MapParseError::Io(unsupported()));
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::PlaneDir;
+
+	#[test]
+	pub fn test_load_ascii() {
+		let map = load_ascii("#.#\ns r\n^/.\n").unwrap();
+		assert_eq!(map.footprint(), (3, 3));
+		let ty = |x, y| map.get(Elevation(0), &Coord{x: x, y: y}).map(|t| t.ty);
+		assert_eq!(ty(0, 0), Some(TileType::Impasse));
+		assert_eq!(ty(1, 0), Some(TileType::Freewalk));
+		assert_eq!(ty(0, 1), Some(TileType::Sidewalk));
+		assert_eq!(ty(1, 1), None);
+		assert_eq!(ty(2, 1), Some(TileType::Road));
+		assert_eq!(ty(0, 2), Some(TileType::Stair));
+		assert_eq!(ty(1, 2), Some(TileType::Ramp));
+		assert!(!map.get(Elevation(0), &Coord{x: 0, y: 0}).unwrap().is_enterable_from(&PlaneDir::North));
+	}
+
+	#[test]
+	pub fn test_ascii_round_trip() {
+		let text = "\
+##########
+#....s...#
+#.rr s  ^#
+#..../...#
+##########";
+		let map = load_ascii(text).unwrap();
+		assert_eq!(to_ascii(&map, Elevation(0)).trim_end(), text);
+		assert_eq!(to_ascii_with(&map, Elevation(0), '_').lines().nth(2), Some("#.rr_s__^#"));
+
+		// a layer that isn't there comes out blank
+		assert_eq!(to_ascii_with(&map, Elevation(3), '_').lines().next(), Some("__________"));
+	}
+
+	#[test]
+	pub fn test_load_ascii_errors() {
+		match load_ascii("...\n..\n...") {
+			Err(MapParseError::Ragged{line: 2, expected: 3, found: 2}) => (),
+			other => panic!("expected a ragged line error, got {:?}", other.map(|m| m.footprint())),
+		}
+		match load_ascii("...\n..x") {
+			Err(MapParseError::UnknownChar{line: 2, column: 3, ch: 'x'}) => (),
+			other => panic!("expected an unknown char error, got {:?}", other.map(|m| m.footprint())),
+		}
+		assert!(matches!(load_ascii(""), Err(MapParseError::Empty)));
+	}
+
+	#[test]
+	pub fn test_csv_round_trip() {
+		let map = load_ascii("#.s\nr ^\n").unwrap();
+		let text = to_csv(&map, Elevation(0));
+		assert_eq!(text, "Impasse,Freewalk,Sidewalk\nRoad,,Stair\n");
+		let loaded = from_csv(&text).unwrap();
+		assert_eq!(to_ascii(&loaded, Elevation(0)), to_ascii(&map, Elevation(0)));
+
+		assert!(matches!(from_csv("Road,Lava"),
+			Err(MapParseError::UnknownName{line: 1, column: 2, ..})));
+		assert!(matches!(from_csv("Road,Road\nRoad"), Err(MapParseError::Ragged{line: 2, ..})));
+	}
+
+	#[test]
+	pub fn test_image_map() {
+		let palette = HashMap::from([([255, 255, 255], TileType::Freewalk), ([0, 0, 0], TileType::Impasse)]);
+		let mut img = image::RgbImage::from_pixel(3, 2, image::Rgb([255, 255, 255]));
+		img.put_pixel(1, 0, image::Rgb([0, 0, 0]));
+
+		let path = std::env::temp_dir().join(format!("walk-image-{}.png", std::process::id()));
+		img.save(&path).unwrap();
+		let loaded = load_image_map(&path, &palette);
+		std::fs::remove_file(&path).unwrap();
+		let map = loaded.unwrap();
+		assert_eq!(map.footprint(), (3, 2));
+		assert_eq!(to_ascii(&map, Elevation(0)), ".#.\n...\n");
+
+		img.put_pixel(2, 1, image::Rgb([255, 0, 0]));
+		assert!(matches!(image_to_map(&img, &palette),
+			Err(MapImportError::UnknownColor{x: 2, y: 1, color: [255, 0, 0]})));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_from_ron() {
+		let mut map = TileMap::new(3, 2);
+		map.set(Elevation(0), &Coord{x: 2, y: 1}, Some(Tile::from_template(TileDirTemplate::Stair, PlaneDir::South)));
+		let text = map.to_ron().unwrap();
+		let loaded = TileMap::from_ron(&text).unwrap();
+		assert!(loaded == map);
+
+		map.layers.insert(Elevation(1), Grid::new(2, 2));
+		let text = map.to_ron().unwrap();
+		assert!(matches!(TileMap::from_ron(&text), Err(MapParseError::BadLayer(Elevation(1)))));
+		assert!(matches!(TileMap::from_ron("not a map"), Err(MapParseError::Ron(_))));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_json_round_trip() {
+		let mut map = TileMap::new(3, 3);
+		map.set(Elevation(0), &Coord{x: 0, y: 1}, Some(Tile::from_template(TileDirTemplate::Ramp, PlaneDir::West)));
+		map.set(Elevation(2), &Coord{x: 2, y: 2}, Some(Tile::from_type(TileType::Road, PlaneDir::North)));
+		let text = map.to_json().unwrap();
+		assert!(text.contains("\"2\""));
+		assert!(TileMap::from_json(&text).unwrap() == map);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_json_errors() {
+		assert!(matches!(TileMap::from_json("{\"layers\": "), Err(MapParseError::Json(_))));
+		assert!(matches!(TileMap::from_json("[1, 2, 3]"), Err(MapParseError::Json(_))));
+
+		let mut map = TileMap::new(2, 2);
+		map.layers.insert(Elevation(-1), Grid::new(3, 2));
+		let text = map.to_json().unwrap();
+		assert!(matches!(TileMap::from_json(&text), Err(MapParseError::BadLayer(Elevation(-1)))));
+
+		// sizes that agree with each other but can't be multiplied out
+		let n = usize::MAX;
+		let text = format!(r#"{{"format": 1, "layers": {{"0": {{"tile_array": [], "width": {n}, "height": {n}}}}}, "width": {n}, "height": {n}}}"#);
+		assert!(matches!(TileMap::from_json(&text), Err(MapParseError::BadLayer(Elevation(0)))));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_stable_output() {
+		let build = |elevations: &[Elevation]| {
+			let mut map = TileMap::new(2, 2);
+			for e in elevations {
+				map.set(*e, &Coord{x: 1, y: 0}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+			}
+			return map;
+		};
+		let a = build(&[3, -1, 0, 2, 1].map(Elevation));
+		let b = build(&[1, 2, 0, -1, 3].map(Elevation));
+		assert_eq!(a.to_ron().unwrap(), b.to_ron().unwrap());
+		assert_eq!(a.to_json().unwrap(), b.to_json().unwrap());
+
+		let text = a.to_json().unwrap();
+		let find = |s: &str| text.find(s).unwrap();
+		assert!(find("\"-1\"") < find("\"0\"") && find("\"0\"") < find("\"3\""));
+		let edges = &text[find("dir_infos")..];
+		let find = |s: &str| edges.find(s).unwrap();
+		assert!(find("North") < find("East") && find("East") < find("South") && find("South") < find("West"));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_format_version() {
+		let mut map = TileMap::new(2, 1);
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(Tile::from_type(TileType::Road, PlaneDir::North)));
+		let text = map.to_ron().unwrap();
+		assert!(text.contains(&format!("format: {}", FORMAT_VERSION)));
+		assert!(TileMap::from_ron(&text).unwrap() == map);
+
+		let future = text.replace(&format!("format: {}", FORMAT_VERSION), "format: 99");
+		assert!(matches!(TileMap::from_ron(&future),
+			Err(MapParseError::UnsupportedVersion{found: 99, expected: FORMAT_VERSION})));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_migrate_untagged() {
+		let mut map = TileMap::new(3, 1);
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		map.set(Elevation(0), &Coord{x: 1, y: 0}, Some(Tile::from_template(TileDirTemplate::Stair, PlaneDir::East)));
+		map.set(Elevation(0), &Coord{x: 2, y: 0}, Some(Tile::from_type(TileType::Sidewalk, PlaneDir::North)));
+
+		// written before the format tag or tile types existed
+		let mut old: serde_json::Value = serde_json::from_str(&map.to_json().unwrap()).unwrap();
+		old.as_object_mut().unwrap().remove("format");
+		for tile in old["layers"]["0"]["tile_array"].as_array_mut().unwrap() {
+			tile.as_object_mut().unwrap().remove("ty");
+		}
+
+		let loaded = TileMap::from_json(&old.to_string()).unwrap();
+		let ty = |x| loaded.get(Elevation(0), &Coord{x: x, y: 0}).unwrap().ty;
+		assert_eq!(ty(0), TileType::Impasse);
+		assert_eq!(ty(1), TileType::Stair);
+		// the type itself is gone, floors come back as the default
+		assert_eq!(ty(2), TileType::Freewalk);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_fragment_ron() {
+		let mut map = load_ascii("....\n.#^.\n..s.\n").unwrap();
+		map.set(Elevation(0), &Coord{x: 2, y: 1}, Some(Tile::from_template(TileDirTemplate::Stair, PlaneDir::West)));
+		let region = crate::editor::Region::from_corners(&Coord{x: 1, y: 1}, &Coord{x: 2, y: 2});
+		let fragment = MapFragment::copy(&map, Elevation(0), &region);
+
+		let loaded = MapFragment::from_ron(&fragment.to_ron().unwrap()).unwrap();
+		assert_eq!(loaded, fragment);
+
+		let mut target = TileMap::new(4, 4);
+		// whatever lands past the right edge is dropped
+		let changes = loaded.stamp(&target, Elevation(0), &Coord{x: 3, y: 0});
+		for (e, c, t) in changes {
+			target.set(e, &c, t);
+		}
+		assert_eq!(to_ascii(&target, Elevation(0)), "   #\n   .\n    \n    \n");
+
+		let changes = loaded.stamp(&target, Elevation(0), &Coord{x: 0, y: 2});
+		for (e, c, t) in changes {
+			target.set(e, &c, t);
+		}
+		assert_eq!(to_ascii(&target, Elevation(0)).lines().skip(2).collect::<Vec<_>>(), ["#^  ", ".s  "]);
+		assert_eq!(target.get(Elevation(0), &Coord{x: 1, y: 2}), map.get(Elevation(0), &Coord{x: 2, y: 1}));
+
+		// an offset nothing could have copied is dropped rather than wrapping round
+		let huge = MapFragment{tiles: vec![(Coord{x: usize::MAX, y: 0}, Tile::new()), (Coord{x: 0, y: 1}, Tile::new())]};
+		let loaded = MapFragment::from_ron(&huge.to_ron().unwrap()).unwrap();
+		let changes = loaded.stamp(&target, Elevation(0), &Coord{x: 1, y: 0});
+		assert_eq!(changes, vec![(Elevation(0), Coord{x: 1, y: 1}, Some(Tile::new()))]);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_load_ron_reader() {
+		let mut map = load_ascii("#..\n.^/\nrs.\n").unwrap();
+		map.set(Elevation(2), &Coord{x: 0, y: 2}, Some(Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::West)));
+		let text = map.to_ron().unwrap();
+		let from_reader = load_ron_reader(io::Cursor::new(text.clone().into_bytes())).unwrap();
+		assert!(from_reader == TileMap::from_ron(&text).unwrap());
+
+		let truncated = io::Cursor::new(text.as_bytes()[..text.len() / 2].to_vec());
+		assert!(matches!(load_ron_reader(truncated), Err(MapParseError::Ron(_))));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_layer_meta_round_trip() {
+		let mut map = load_ascii("..\n.#\n").unwrap();
+		map.set(Elevation(1), &Coord{x: 0, y: 0}, Some(Tile::from_type(TileType::Stair, PlaneDir::North)));
+		// files without any leave it out, and still load with the defaults
+		let plain = map.to_ron().unwrap();
+		assert!(!plain.contains("layer_meta"));
+		assert_eq!(TileMap::from_ron(&plain).unwrap().layer_meta(Elevation(1)), LayerMeta::unnamed(Elevation(1)));
+
+		let ground = LayerMeta{name: "Ground".to_string(), tint: [0.5, 0.9, 0.5, 0.25]};
+		map.set_layer_meta(Elevation(0), ground.clone());
+		let loaded = TileMap::from_ron(&map.to_ron().unwrap()).unwrap();
+		assert!(loaded == map);
+		assert_eq!(loaded.layer_meta(Elevation(0)), ground);
+		assert_eq!(loaded.layer_meta(Elevation(1)), LayerMeta::unnamed(Elevation(1)));
+		assert!(TileMap::from_json(&map.to_json().unwrap()).unwrap() == map);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_save_load() {
+		let mut map = TileMap::new(4, 4);
+		map.set(Elevation(0), &Coord{x: 1, y: 3}, Some(Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East)));
+		map.set(Elevation(1), &Coord{x: 0, y: 0}, Some(Tile::new()));
+
+		let path = std::env::temp_dir().join(format!("walk-save-{}.ron", std::process::id()));
+		save_map(&map, &path).unwrap();
+		let loaded = load_map(&path);
+		fs::remove_file(&path).unwrap();
+		assert!(loaded.unwrap() == map);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	pub fn test_session_round_trip() {
+		let mut editor = crate::editor::Editor::new();
+		editor.active_elevation = Elevation(-2);
+		editor.current_template = TileDirTemplate::Ramp;
+		editor.current_facing = PlaneDir::West;
+		let state = editor.state((120.5, -40.0), 2.0);
+
+		let path = std::env::temp_dir().join(format!("walk-session-{}.ron", std::process::id()));
+		save_session(&state, &path).unwrap();
+		let loaded = load_session(&path);
+		fs::remove_file(&path).unwrap();
+		let loaded = loaded.unwrap();
+		assert_eq!(loaded, state);
+
+		let mut restored = crate::editor::Editor::new();
+		restored.restore(&loaded);
+		assert_eq!((restored.active_elevation, restored.current_template, restored.current_facing), (Elevation(-2), TileDirTemplate::Ramp, PlaneDir::West));
+		assert_eq!(loaded.camera_target, (120.5, -40.0));
+
+		// fields a newer or older file doesn't have keep their defaults
+		let partial = EditorState::from_ron("(active_elevation: 3)").unwrap();
+		assert_eq!(partial, EditorState{active_elevation: Elevation(3), ..EditorState::default()});
+		assert!(EditorState::from_ron("(active_elevation: ").is_err());
+		assert!(matches!(load_session(&path), Err(MapParseError::Io(_))));
+	}
+}
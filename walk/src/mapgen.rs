@@ -0,0 +1,210 @@
+use crate::{Coord, Grid, PlaneDir, Tile, TileType};
+
+// splitmix64, quick and fully pinned down by the seed. the generators
+// don't need anything better and the library can't pull in a window's rand
+struct Rng(u64);
+
+impl Rng {
+	fn new(seed: u64) -> Rng {
+		Rng(seed)
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		return z ^ (z >> 31);
+	}
+
+	// in 0.0..1.0
+	fn unit(&mut self) -> f32 {
+		(self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+	}
+
+	// in 0..n, n has to be more than 0
+	fn below(&mut self, n: usize) -> usize {
+		(self.next_u64() % n as u64) as usize
+	}
+}
+
+// chance of each type per tile, whatever is left over is freewalk
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScatterOdds {
+	pub impasse: f32,
+	pub road: f32,
+	pub sidewalk: f32,
+}
+
+impl Default for ScatterOdds {
+	fn default() -> ScatterOdds {
+		ScatterOdds{impasse: 0.15, road: 0.1, sidewalk: 0.2}
+	}
+}
+
+pub fn generate_random(width: usize, height: usize, seed: u64) -> Grid<Tile> {
+	generate_random_with(width, height, seed, &ScatterOdds::default())
+}
+
+// every cell gets a tile, the same seed and odds always give the same grid
+pub fn generate_random_with(width: usize, height: usize, seed: u64, odds: &ScatterOdds) -> Grid<Tile> {
+	let mut rng = Rng::new(seed);
+	let mut grid = Grid::new(width, height);
+	for y in 0..height {
+		for x in 0..width {
+			let roll = rng.unit();
+			let ty = if roll < odds.impasse {
+				TileType::Impasse
+			} else if roll < odds.impasse + odds.road {
+				TileType::Road
+			} else if roll < odds.impasse + odds.road + odds.sidewalk {
+				TileType::Sidewalk
+			} else {
+				TileType::Freewalk
+			};
+			grid.add(&Coord{x: x, y: y}, Tile::from_type(ty, Tile::DEFAULT_FACING));
+		}
+	}
+	return grid;
+}
+
+// a perfect maze, exactly one route between any two corridor tiles.
+// corridors run along odd rows and columns, everything else is impasse
+pub fn generate_maze(width: usize, height: usize, seed: u64) -> Grid<Tile> {
+	let mut rng = Rng::new(seed);
+	let mut grid = Grid::new(width, height);
+	for y in 0..height {
+		for x in 0..width {
+			grid.add(&Coord{x: x, y: y}, Tile::from_type(TileType::Impasse, Tile::DEFAULT_FACING));
+		}
+	}
+	if width < 3 || height < 3 {
+		return grid;
+	}
+	let floor = || Tile::from_type(TileType::Freewalk, Tile::DEFAULT_FACING);
+	let is_cell = |c: &Coord| c.x % 2 == 1 && c.y % 2 == 1 && c.x < width - 1 && c.y < height - 1;
+
+	// randomized depth first search, knocking through the wall to each new cell
+	let start = Coord{x: 1, y: 1};
+	grid.add(&start, floor());
+	let mut stack = vec![start];
+	while let Some(&c) = stack.last() {
+		let unvisited: Vec<(Coord, Coord)> = PlaneDir::ALL.into_iter()
+			.filter_map(|d| {
+				let wall = c.step(d)?;
+				let next = wall.step(d)?;
+				let fresh = is_cell(&next) && grid.get(&next).as_ref().is_some_and(|t| t.ty == TileType::Impasse);
+				fresh.then_some((wall, next))
+			})
+			.collect();
+		if unvisited.is_empty() {
+			stack.pop();
+			continue;
+		}
+		let (wall, next) = unvisited[rng.below(unvisited.len())];
+		grid.add(&wall, floor());
+		grid.add(&next, floor());
+		stack.push(next);
+	}
+	return grid;
+}
+
+// about this much of a cave starts out as rock before smoothing
+const CAVE_FILL: f32 = 0.45;
+
+// rock scattered at random then smoothed iterations times, a cell turns to
+// rock with 5 or more rock cells around it and stays rock with 4. the edge
+// of the map counts as rock so caves come out walled in. the floor isn't
+// always one piece, check it with path::reachable if that matters
+pub fn generate_cave(width: usize, height: usize, seed: u64, iterations: usize) -> Grid<Tile> {
+	let mut rng = Rng::new(seed);
+	let mut rock = Grid::<()>::new(width, height);
+	for y in 0..height {
+		for x in 0..width {
+			if rng.unit() < CAVE_FILL {
+				rock.add(&Coord{x: x, y: y}, ());
+			}
+		}
+	}
+	for _ in 0..iterations {
+		let mut next = Grid::new(width, height);
+		for y in 0..height {
+			for x in 0..width {
+				let c = Coord{x: x, y: y};
+				let around = rock.occupied_neighbor_count(&c, true, true);
+				if around >= 5 || (around == 4 && rock.occupied(&c)) {
+					next.add(&c, ());
+				}
+			}
+		}
+		rock = next;
+	}
+
+	let mut grid = Grid::new(width, height);
+	for (c, cell) in rock.cells() {
+		let ty = if cell.is_some() { TileType::Impasse } else { TileType::Freewalk };
+		grid.add(&c, Tile::from_type(ty, Tile::DEFAULT_FACING));
+	}
+	return grid;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::path::{connected_components, reachable};
+
+	#[test]
+	pub fn test_generate_random() {
+		let a = generate_random(16, 12, 7);
+		assert_eq!(a, generate_random(16, 12, 7));
+		assert_eq!(a.count_occupied(), 16 * 12);
+		assert_ne!(a, generate_random(16, 12, 8));
+
+		let walls = ScatterOdds{impasse: 1.0, road: 0.0, sidewalk: 0.0};
+		let g = generate_random_with(4, 4, 1, &walls);
+		assert!(g.iter().all(|(_, t)| t.ty == TileType::Impasse));
+	}
+
+	#[test]
+	pub fn test_generate_maze() {
+		for (width, height, seed) in [(21, 15, 1), (20, 16, 2), (9, 9, 3)] {
+			let maze = generate_maze(width, height, seed);
+			let corridors = maze.iter().filter(|(_, t)| t.ty == TileType::Freewalk).count();
+			// a perfect maze over odd cells opens one wall less than it has cells
+			let cells = ((width - 1) / 2) * ((height - 1) / 2);
+			assert_eq!(corridors, 2 * cells - 1);
+			assert_eq!(reachable(&maze, Coord{x: 1, y: 1}).len(), corridors);
+
+			let components = connected_components(&maze);
+			assert_eq!(components.len(), corridors);
+			assert!(components.values().all(|id| *id == 0));
+		}
+		assert_eq!(generate_maze(21, 15, 5), generate_maze(21, 15, 5));
+		assert!(generate_maze(2, 5, 1).iter().all(|(_, t)| t.ty == TileType::Impasse));
+	}
+
+	#[test]
+	pub fn test_generate_cave() {
+		let cave = generate_cave(40, 30, 11, 4);
+		assert_eq!(cave, generate_cave(40, 30, 11, 4));
+		assert_ne!(cave, generate_cave(40, 30, 12, 4));
+		assert_ne!(cave, generate_cave(40, 30, 11, 5));
+		assert_eq!(cave.count_occupied(), 40 * 30);
+		assert!(cave.iter().all(|(_, t)| t.ty == TileType::Impasse || t.ty == TileType::Freewalk));
+
+		// corners have 5 cells off the map around them so always end up rock
+		for c in [(0, 0), (39, 0), (0, 29), (39, 29)] {
+			assert_eq!(cave.get(&Coord::from(&c)).as_ref().unwrap().ty, TileType::Impasse);
+		}
+		let edge_rock = cave.iter().filter(|(c, t)| c.x == 0 && t.ty == TileType::Impasse).count();
+		assert!(edge_rock > 15);
+		let start = cave.find(|t| t.ty == TileType::Freewalk).unwrap();
+		let room = reachable(&cave, start);
+		assert!(room.contains(&start));
+		assert!(room.iter().all(|c| cave.get(c).as_ref().unwrap().ty == TileType::Freewalk));
+
+		// without smoothing it's just the noise
+		let noise = generate_cave(40, 30, 11, 0);
+		assert!(noise.iter().any(|(c, t)| c.x == 0 && t.ty == TileType::Freewalk));
+	}
+}
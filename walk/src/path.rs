@@ -0,0 +1,1361 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::{Coord, DirInfo, Elevation, Grid, PlaneDir, Tile, TileMap};
+
+// open set entry, the heap pops the lowest estimate first and breaks
+// ties by insertion order so results don't depend on hashing
+struct Open<N> {
+	estimate: u32,
+	seq: usize,
+	node: N,
+}
+
+impl<N> PartialEq for Open<N> {
+	fn eq(&self, other: &Self) -> bool {
+		(self.estimate, self.seq) == (other.estimate, other.seq)
+	}
+}
+
+impl<N> Eq for Open<N> {}
+
+impl<N> PartialOrd for Open<N> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<N> Ord for Open<N> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		(other.estimate, other.seq).cmp(&(self.estimate, self.seq))
+	}
+}
+
+// the frontier A* takes its next node from. pop has to give the lowest
+// estimate first and, between equal estimates, whatever was pushed first,
+// so every implementation explores in the same order
+pub trait OpenSet<N> {
+	fn push(&mut self, estimate: u32, seq: usize, node: N);
+	fn pop(&mut self) -> Option<N>;
+}
+
+#[derive(Default)]
+pub struct HeapOpenSet<N> {
+	heap: BinaryHeap<Open<N>>,
+}
+
+impl<N> HeapOpenSet<N> {
+	pub fn new() -> HeapOpenSet<N> {
+		HeapOpenSet{heap: BinaryHeap::new()}
+	}
+}
+
+impl<N> OpenSet<N> for HeapOpenSet<N> {
+	fn push(&mut self, estimate: u32, seq: usize, node: N) {
+		self.heap.push(Open{estimate: estimate, seq: seq, node: node});
+	}
+
+	fn pop(&mut self) -> Option<N> {
+		self.heap.pop().map(|o| o.node)
+	}
+}
+
+// one fifo per estimate, good when estimates are small integers. pushes
+// come in seq order, so a bucket is already sorted by it
+#[derive(Default)]
+pub struct BucketOpenSet<N> {
+	buckets: Vec<VecDeque<N>>,
+	// no bucket below this one holds anything
+	lowest: usize,
+	len: usize,
+}
+
+impl<N> BucketOpenSet<N> {
+	pub fn new() -> BucketOpenSet<N> {
+		BucketOpenSet{buckets: Vec::new(), lowest: 0, len: 0}
+	}
+}
+
+impl<N> OpenSet<N> for BucketOpenSet<N> {
+	fn push(&mut self, estimate: u32, _seq: usize, node: N) {
+		let i = estimate as usize;
+		if i >= self.buckets.len() {
+			self.buckets.resize_with(i + 1, VecDeque::new);
+		}
+		self.buckets[i].push_back(node);
+		self.lowest = self.lowest.min(i);
+		self.len += 1;
+	}
+
+	fn pop(&mut self) -> Option<N> {
+		if self.len == 0 {
+			return None;
+		}
+		while self.buckets[self.lowest].is_empty() {
+			self.lowest += 1;
+		}
+		self.len -= 1;
+		return self.buckets[self.lowest].pop_front();
+	}
+}
+
+// what a search did, for comparing open sets
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+	// nodes taken off the open set and looked past
+	pub expanded: usize,
+	pub pushed: usize,
+}
+
+// A* over any graph. neighbors gives each reachable node with the cost of
+// stepping to it, heuristic must never overestimate the remaining cost.
+// returns the path including both ends and its total cost
+fn search<N, I>(
+	start: N,
+	is_goal: impl Fn(&N) -> bool,
+	neighbors: impl FnMut(&N) -> I,
+	heuristic: impl Fn(&N) -> u32,
+) -> Option<(Vec<N>, u32)>
+where
+	N: Copy + Eq + Hash,
+	I: IntoIterator<Item = (N, u32)>,
+{
+	let mut stats = SearchStats::default();
+	search_with(HeapOpenSet::new(), &mut stats, start, is_goal, neighbors, heuristic)
+}
+
+// as search, on the given open set and counting what it does into stats
+fn search_with<N, I>(
+	mut open: impl OpenSet<N>,
+	stats: &mut SearchStats,
+	start: N,
+	is_goal: impl Fn(&N) -> bool,
+	mut neighbors: impl FnMut(&N) -> I,
+	heuristic: impl Fn(&N) -> u32,
+) -> Option<(Vec<N>, u32)>
+where
+	N: Copy + Eq + Hash,
+	I: IntoIterator<Item = (N, u32)>,
+{
+	let mut best: HashMap<N, u32> = HashMap::new();
+	let mut came_from: HashMap<N, N> = HashMap::new();
+	// a node pushed again at a lower cost leaves its old entry behind, which
+	// is skipped rather than looked past a second time
+	let mut closed: HashSet<N> = HashSet::new();
+	let mut seq = 0;
+
+	best.insert(start, 0);
+	open.push(heuristic(&start), seq, start);
+	stats.pushed += 1;
+
+	while let Some(node) = open.pop() {
+		if !closed.insert(node) {
+			continue;
+		}
+		let cost = best[&node];
+		if is_goal(&node) {
+			let mut path = vec![node];
+			let mut cur = node;
+			while let Some(prev) = came_from.get(&cur) {
+				path.push(*prev);
+				cur = *prev;
+			}
+			path.reverse();
+			return Some((path, cost));
+		}
+
+		stats.expanded += 1;
+		for (next, step) in neighbors(&node) {
+			let Some(next_cost) = cost.checked_add(step) else {
+				continue;
+			};
+			if best.get(&next).is_some_and(|c| *c <= next_cost) {
+				continue;
+			}
+			best.insert(next, next_cost);
+			came_from.insert(next, node);
+			seq += 1;
+			open.push(next_cost.saturating_add(heuristic(&next)), seq, next);
+			stats.pushed += 1;
+		}
+	}
+	return None;
+}
+
+// neighbours a walker on this layer can step to from coord
+fn grid_steps(grid: &Grid<Tile>, coord: &Coord) -> Vec<Coord> {
+	let Some(here) = grid.get(coord) else {
+		return Vec::new();
+	};
+	grid.neighbors_with_dir(coord).into_iter()
+		.filter(|(dir, c)| steps_to(grid, here, dir, c))
+		.map(|(_, c)| c)
+		.collect()
+}
+
+// whether here can be left through dir onto c. entering is up to can_enter,
+// then the two edges have to meet
+fn steps_to(grid: &Grid<Tile>, here: &Tile, dir: &PlaneDir, c: &Coord) -> bool {
+	if !grid.can_enter(c, dir.opposite()) {
+		return false;
+	}
+	let there = grid.get(c).as_ref().expect("can_enter only lets tiles through");
+	return here.connects(dir, there);
+}
+
+// like grid_steps, with the tile stepped onto and the cost of the edge crossed
+fn grid_crossings<'g>(grid: &'g Grid<Tile>, coord: &Coord) -> Vec<(Coord, &'g Tile, u32)> {
+	let Some(here) = grid.get(coord) else {
+		return Vec::new();
+	};
+	grid.neighbors_with_dir(coord).into_iter()
+		.filter(|(dir, c)| steps_to(grid, here, dir, c))
+		.map(|(dir, c)| {
+			let there = grid.get(&c).as_ref().expect("steps_to only lets tiles through");
+			(c, there, here.crossing_cost(&dir, there))
+		})
+		.collect()
+}
+
+// per cell bits for one layer so expansion doesn't have to look at tiles.
+// bit d says the edge towards PlaneDir d can be entered, bit 4 + d that a
+// walker can step to the neighbour that way. empty cells are all zero
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalkabilityGrid {
+	width: usize,
+	height: usize,
+	masks: Vec<u8>,
+	// layer and map version it was built from, if built from a map
+	source: Option<(Elevation, (u64, u64))>,
+}
+
+impl WalkabilityGrid {
+	pub fn new(grid: &Grid<Tile>) -> WalkabilityGrid {
+		let mut masks = vec![0; grid.width * grid.height];
+		for (c, tile) in grid.iter() {
+			let mut mask = 0;
+			for d in PlaneDir::ALL {
+				if tile.is_enterable_from(&d) {
+					mask |= 1 << d as u8;
+				}
+			}
+			for (d, n) in grid.neighbors_with_dir(&c) {
+				if steps_to(grid, tile, &d, &n) {
+					mask |= 1 << (4 + d as u8);
+				}
+			}
+			masks[c.x + c.y * grid.width] = mask;
+		}
+		return WalkabilityGrid{width: grid.width, height: grid.height, masks: masks, source: None};
+	}
+
+	// a missing layer has nothing to walk on
+	pub fn from_map(map: &TileMap, elevation: Elevation) -> WalkabilityGrid {
+		let mut walk = match map.layer(elevation) {
+			Some(g) => WalkabilityGrid::new(g),
+			None => {
+				let (width, height) = map.footprint();
+				WalkabilityGrid{width: width, height: height, masks: vec![0; width * height], source: None}
+			},
+		};
+		walk.source = Some((elevation, map.revision()));
+		return walk;
+	}
+
+	// false once the map has been edited since this was built
+	pub fn is_current(&self, map: &TileMap) -> bool {
+		self.source.is_some_and(|(_, revision)| revision == map.revision())
+	}
+
+	// rebuilds from the same layer if the map changed. does nothing for one
+	// made with new, there's no map to go back to
+	pub fn refresh(&mut self, map: &TileMap) {
+		if let Some((elevation, _)) = self.source && !self.is_current(map) {
+			*self = WalkabilityGrid::from_map(map, elevation);
+		}
+	}
+
+	fn mask(&self, coord: &Coord) -> u8 {
+		if coord.x >= self.width || coord.y >= self.height {
+			return 0;
+		}
+		self.masks[coord.x + coord.y * self.width]
+	}
+
+	pub fn is_enterable_from(&self, coord: &Coord, dir: PlaneDir) -> bool {
+		self.mask(coord) & (1 << dir as u8) != 0
+	}
+
+	// as grid_steps, clearing and refilling buf
+	pub fn steps_into(&self, coord: &Coord, buf: &mut Vec<Coord>) {
+		buf.clear();
+		let mask = self.mask(coord);
+		for d in PlaneDir::ALL {
+			if mask & (1 << (4 + d as u8)) != 0 {
+				buf.push(coord.step(d).expect("only in-bounds steps get a bit"));
+			}
+		}
+	}
+}
+
+// shortest route between two tiles on one layer, every step costs the same
+pub fn astar(grid: &Grid<Tile>, start: Coord, goal: Coord) -> Option<Vec<Coord>> {
+	astar_with(grid, start, goal, HeapOpenSet::new()).0
+}
+
+// astar on the given open set, also saying how much work it took
+pub fn astar_with(grid: &Grid<Tile>, start: Coord, goal: Coord, open: impl OpenSet<Coord>) -> (Option<Vec<Coord>>, SearchStats) {
+	let mut stats = SearchStats::default();
+	if grid.get(&start).is_none() || grid.get(&goal).is_none() {
+		return (None, stats);
+	}
+	let found = search_with(
+		open,
+		&mut stats,
+		start,
+		|c| *c == goal,
+		|c| grid_steps(grid, c).into_iter().map(|n| (n, 1)),
+		|c| c.manhattan_distance(&goal) as u32,
+	);
+	return (found.map(|(path, _)| path), stats);
+}
+
+// how a cell looks to the jump search in astar_fast
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Cell {
+	// off the grid, empty, or walled in on every side
+	Blocked,
+	// plain floor, open all round and level
+	Free,
+	// anything else, part walls, stairs and so on
+	Special,
+}
+
+struct Jumper<'a> {
+	grid: &'a Grid<Tile>,
+	goal: Coord,
+}
+
+impl Jumper<'_> {
+	fn cell(&self, coord: Option<Coord>) -> Cell {
+		let Some(c) = coord else {
+			return Cell::Blocked;
+		};
+		if !self.grid.contains(&c) {
+			return Cell::Blocked;
+		}
+		match self.grid.get(&c) {
+			None => Cell::Blocked,
+			Some(t) if t.blocks() => Cell::Blocked,
+			Some(t) if t.edges().all(|(_, info)| info == DirInfo::OPEN) => Cell::Free,
+			Some(_) => Cell::Special,
+		}
+	}
+
+	fn free(&self, coord: Option<Coord>) -> bool {
+		self.cell(coord) == Cell::Free
+	}
+
+	// next to something that isn't plain floor, these get expanded a step at a time
+	fn near_special(&self, c: &Coord) -> bool {
+		self.cell(Some(*c)) == Cell::Special ||
+			PlaneDir::ALL.iter().any(|d| self.cell(c.step(*d)) == Cell::Special)
+	}
+
+	// runs straight from from towards dir, skipping over open floor, and stops
+	// at the first cell a shortest path might turn at
+	fn jump(&self, from: &Coord, dir: PlaneDir) -> Option<Coord> {
+		let mut c = *from;
+		loop {
+			let next = c.step(dir);
+			if !self.free(next) {
+				return None;
+			}
+			c = next.expect("free cells are on the grid");
+			if c == self.goal || self.near_special(&c) {
+				return Some(c);
+			}
+			if is_vertical(dir) {
+				if self.forced_sides(&c, dir).next().is_some() {
+					return Some(c);
+				}
+			} else if [PlaneDir::North, PlaneDir::South].iter().any(|v| self.jump(&c, *v).is_some()) {
+				return Some(c);
+			}
+		}
+	}
+
+	// paths go across first and then up or down, so after moving vertically a
+	// sideways turn is only needed when the cell beside the previous one is shut
+	fn forced_sides(&self, c: &Coord, dir: PlaneDir) -> impl Iterator<Item = PlaneDir> {
+		[dir.clockwise(), dir.anticlockwise()].into_iter().filter(move |side| {
+			let beside = c.step(*side);
+			let behind = beside.and_then(|b| b.step(dir.opposite()));
+			self.free(beside) && !self.free(behind)
+		})
+	}
+
+	fn successors(&self, node: &(Coord, Option<PlaneDir>)) -> Vec<((Coord, Option<PlaneDir>), u32)> {
+		let (c, arrived) = *node;
+		if self.near_special(&c) || self.cell(Some(c)) != Cell::Free {
+			return grid_steps(self.grid, &c).into_iter()
+				.map(|n| ((n, PlaneDir::from_offset(n.x as isize - c.x as isize, n.y as isize - c.y as isize)), 1))
+				.collect();
+		}
+		let dirs: Vec<PlaneDir> = match arrived {
+			None => PlaneDir::ALL.to_vec(),
+			Some(d) if is_vertical(d) => [d].into_iter().chain(self.forced_sides(&c, d)).collect(),
+			Some(d) => vec![d, PlaneDir::North, PlaneDir::South],
+		};
+		dirs.into_iter()
+			.filter_map(|d| self.jump(&c, d).map(|j| ((j, Some(d)), c.manhattan_distance(&j) as u32)))
+			.collect()
+	}
+}
+
+fn is_vertical(dir: PlaneDir) -> bool {
+	matches!(dir, PlaneDir::North | PlaneDir::South)
+}
+
+// same result as astar, but on open floor it jumps along straight runs
+// instead of queueing every tile. near walls and anything that isn't plain
+// floor it goes back to ordinary single steps
+pub fn astar_fast(grid: &Grid<Tile>, start: Coord, goal: Coord) -> Option<Vec<Coord>> {
+	if grid.get(&start).is_none() || grid.get(&goal).is_none() {
+		return None;
+	}
+	let jumper = Jumper{grid: grid, goal: goal};
+	let (jumps, _) = search(
+		(start, None),
+		|(c, _)| *c == goal,
+		|n| jumper.successors(n),
+		|(c, _)| c.manhattan_distance(&goal) as u32,
+	)?;
+
+	// every jump is a straight line, fill them back in
+	let mut path = vec![start];
+	for pair in jumps.windows(2) {
+		path.extend(pair[0].0.line_to(&pair[1].0).into_iter().skip(1));
+	}
+	return Some(path);
+}
+
+// cheapest route on one layer, stepping onto a tile costs cost(tile) plus
+// the cost of the edge crossed to get there. tiles costing u32::MAX are never
+// entered
+pub fn dijkstra(
+	map: &TileMap,
+	layer: Elevation,
+	start: Coord,
+	goal: Coord,
+	cost: impl Fn(&Tile) -> u32,
+) -> Option<(Vec<Coord>, u32)> {
+	let grid = map.layer(layer)?;
+	if grid.get(&start).is_none() || grid.get(&goal).is_none() {
+		return None;
+	}
+	return search(
+		start,
+		|c| *c == goal,
+		|c| grid_crossings(grid, c).into_iter()
+			.filter_map(|(n, there, edge)| {
+				let tile_cost = cost(there);
+				(tile_cost != u32::MAX).then(|| (n, tile_cost.saturating_add(edge)))
+			}),
+		|_| 0,
+	);
+}
+
+// shortest route through the whole map, climbing stairs and ramps as needed
+pub fn astar_3d(map: &TileMap, start: (Elevation, Coord), goal: (Elevation, Coord)) -> Option<Vec<(Elevation, Coord)>> {
+	astar_3d_with(map, start, goal, false)
+}
+
+// a tile anywhere on the map
+pub type MapPos = (Elevation, Coord);
+
+// astar_3d for each (start, goal) pair, results in the same order. spread
+// over threads with the parallel feature, one after another without it
+pub fn astar_batch(map: &TileMap, requests: &[(MapPos, MapPos)]) -> Vec<Option<Vec<MapPos>>> {
+	#[cfg(feature = "parallel")]
+	let routes = requests.par_iter().map(|(start, goal)| astar_3d(map, *start, *goal)).collect();
+	#[cfg(not(feature = "parallel"))]
+	let routes = requests.iter().map(|(start, goal)| astar_3d(map, *start, *goal)).collect();
+	return routes;
+}
+
+// as astar_3d, but with wheelchair set only tiles whose type is wheelchair
+// accessible get walked on, so ramps but no stairs
+pub fn astar_3d_with(
+	map: &TileMap,
+	start: (Elevation, Coord),
+	goal: (Elevation, Coord),
+	wheelchair: bool,
+) -> Option<Vec<(Elevation, Coord)>> {
+	let usable = |(e, c): &(Elevation, Coord)| {
+		map.get(*e, c).is_some_and(|t| !wheelchair || t.ty.is_wheelchair_accessible())
+	};
+	if !usable(&start) || !usable(&goal) {
+		return None;
+	}
+	let (path, _) = search(
+		start,
+		|n| *n == goal,
+		|(e, c)| map.walkable_neighbors(*e, c).into_iter().filter(usable).map(|n| (n, 1)),
+		// a stair step moves up and across at once, so take the larger of the
+		// two distances rather than their sum to never overestimate
+		|(e, c)| (c.manhattan_distance(&goal.1) as u32).max(e.abs_diff(goal.0)),
+	)?;
+	return Some(path);
+}
+
+// whether b can be seen from a along a bresenham line. every tile in
+// between has to exist and not be an impasse. a diagonal step squeezes past
+// the two tiles beside it and only needs one of them to be clear
+pub fn line_of_sight(grid: &Grid<Tile>, a: Coord, b: Coord) -> bool {
+	let clear = |c: &Coord| grid.contains(c) && grid.get(c).as_ref().is_some_and(|t| !t.blocks());
+	let line = a.line_to(&b);
+	for (i, pair) in line.windows(2).enumerate() {
+		let (from, to) = (pair[0], pair[1]);
+		if from.x != to.x && from.y != to.y {
+			let beside = [Coord{x: to.x, y: from.y}, Coord{x: from.x, y: to.y}];
+			if !beside.iter().any(clear) {
+				return false;
+			}
+		}
+		// the far end itself may be a wall, that's what's being looked at
+		if i + 2 < line.len() && !clear(&to) {
+			return false;
+		}
+	}
+	return true;
+}
+
+// remembers astar results until the map changes
+#[derive(Debug, Default)]
+pub struct PathCache {
+	routes: HashMap<(Elevation, Coord, Coord), Option<Vec<Coord>>>,
+	// the map revision the routes were found on, see TileMap::revision
+	made_for: Option<(u64, u64)>,
+	pub hits: usize,
+	pub misses: usize,
+}
+
+impl PathCache {
+	pub fn new() -> PathCache {
+		PathCache::default()
+	}
+
+	// room for that many routes before the cache has to grow
+	pub fn with_capacity(routes: usize) -> PathCache {
+		PathCache{routes: HashMap::with_capacity(routes), ..PathCache::default()}
+	}
+
+	pub fn route(&mut self, map: &TileMap, layer: Elevation, start: Coord, goal: Coord) -> Option<&Vec<Coord>> {
+		if self.made_for != Some(map.revision()) {
+			self.routes.clear();
+			self.made_for = Some(map.revision());
+		}
+		let key = (layer, start, goal);
+		if self.routes.contains_key(&key) {
+			self.hits += 1;
+		} else {
+			self.misses += 1;
+			let path = map.layer(layer).and_then(|g| astar(g, start, goal));
+			self.routes.insert(key, path);
+		}
+		return self.routes[&key].as_ref();
+	}
+}
+
+// every tile a walker starting at start can get to on this layer,
+// including start itself. empty if start has no tile
+pub fn reachable(grid: &Grid<Tile>, start: Coord) -> HashSet<Coord> {
+	if grid.get(&start).is_none() {
+		return HashSet::new();
+	}
+	return flood(&WalkabilityGrid::new(grid), start);
+}
+
+fn flood(walk: &WalkabilityGrid, start: Coord) -> HashSet<Coord> {
+	let mut seen = HashSet::from([start]);
+	let mut queue = VecDeque::from([start]);
+	let mut steps = Vec::with_capacity(4);
+	while let Some(c) = queue.pop_front() {
+		walk.steps_into(&c, &mut steps);
+		for n in steps.iter().copied() {
+			if seen.insert(n) {
+				queue.push_back(n);
+			}
+		}
+	}
+	return seen;
+}
+
+// which way to step from each tile to get to goal quickest. cells that
+// can't reach goal are left empty, as is goal itself
+pub fn flow_field(grid: &Grid<Tile>, goal: Coord) -> Grid<PlaneDir> {
+	let mut field = Grid::new(grid.width, grid.height);
+	if grid.get(&goal).is_none() {
+		return field;
+	}
+	let mut seen = HashSet::from([goal]);
+	let mut queue = VecDeque::from([goal]);
+	while let Some(c) = queue.pop_front() {
+		let here = grid.get(&c).as_ref().expect("only tiles get queued");
+		for (dir, n) in grid.neighbors_with_dir(&c) {
+			if steps_to(grid, here, &dir, &n) && seen.insert(n) {
+				field.add(&n, dir.opposite());
+				queue.push_back(n);
+			}
+		}
+	}
+	return field;
+}
+
+// a flow field for one layer, only worked out again when the map or the
+// goal has changed since the last time it was asked for
+#[derive(Debug)]
+pub struct CachedFlowField {
+	layer: Elevation,
+	// goal and map revision the field was made for
+	made_for: Option<(Coord, (u64, u64))>,
+	field: Grid<PlaneDir>,
+	pub computations: usize,
+}
+
+impl CachedFlowField {
+	pub fn new(layer: Elevation) -> CachedFlowField {
+		CachedFlowField{layer: layer, made_for: None, field: Grid::new(0, 0), computations: 0}
+	}
+
+	pub fn get(&mut self, map: &TileMap, goal: Coord) -> &Grid<PlaneDir> {
+		if self.made_for != Some((goal, map.revision())) {
+			self.field = match map.layer(self.layer) {
+				Some(g) => flow_field(g, goal),
+				None => Grid::new(map.width, map.height),
+			};
+			self.made_for = Some((goal, map.revision()));
+			self.computations += 1;
+		}
+		return &self.field;
+	}
+}
+
+// steps from each cell to the nearest one that can't be walked on, either
+// empty or an impasse. obstacles themselves are 0, and everything stays
+// empty if the layer has no obstacles at all
+pub fn distance_to_impasse(grid: &Grid<Tile>) -> Grid<u32> {
+	let mut dist = Grid::new(grid.width, grid.height);
+	let mut queue = VecDeque::new();
+	for (c, slot) in grid.cells() {
+		if slot.as_ref().is_none_or(|t| t.blocks()) {
+			dist.add(&c, 0);
+			queue.push_back(c);
+		}
+	}
+	let mut around = Vec::with_capacity(4);
+	while let Some(c) = queue.pop_front() {
+		let d = dist.get(&c).expect("only measured cells get queued");
+		grid.neighbors_into(&c, &mut around);
+		for n in around.iter().copied() {
+			if dist.get(&n).is_none() {
+				dist.add(&n, d + 1);
+				queue.push_back(n);
+			}
+		}
+	}
+	return dist;
+}
+
+// the direction of each step along a path. consecutive coords that
+// aren't neighbours don't make a step, they're skipped
+pub fn path_directions(path: &[Coord]) -> Vec<PlaneDir> {
+	path.windows(2)
+		.filter_map(|pair| {
+			let dx = pair[1].x as isize - pair[0].x as isize;
+			let dy = pair[1].y as isize - pair[0].y as isize;
+			PlaneDir::from_offset(dx, dy)
+		})
+		.collect()
+}
+
+// region id for every walkable tile, tiles share an id exactly when they
+// can reach each other. ids count up from 0 in row-major order
+pub fn connected_components(grid: &Grid<Tile>) -> HashMap<Coord, u32> {
+	let walk = WalkabilityGrid::new(grid);
+	let mut ids = HashMap::new();
+	let mut next_id = 0;
+	for (c, tile) in grid.iter() {
+		if ids.contains_key(&c) || tile.blocks() {
+			continue;
+		}
+		for member in flood(&walk, c) {
+			ids.insert(member, next_id);
+		}
+		next_id += 1;
+	}
+	return ids;
+}
+
+#[cfg(test)]
+pub mod test {
+	use super::*;
+	use crate::{TileDirTemplate, TileType};
+
+	// builds a layer from rows of '.' for floor, '#' for impasse and ' ' for nothing
+	pub fn grid_from_rows(rows: &[&str]) -> Grid<Tile> {
+		let mut g = Grid::new(rows[0].len(), rows.len());
+		for (y, row) in rows.iter().enumerate() {
+			for (x, ch) in row.chars().enumerate() {
+				let template = match ch {
+					'.' => TileDirTemplate::Floor,
+					'#' => TileDirTemplate::Impasse,
+					_ => continue,
+				};
+				g.add(&Coord{x: x, y: y}, Tile::from_template(template, PlaneDir::North));
+			}
+		}
+		return g;
+	}
+
+	fn assert_walk(path: &[Coord], start: Coord, goal: Coord) {
+		assert_eq!(path.first(), Some(&start));
+		assert_eq!(path.last(), Some(&goal));
+		for pair in path.windows(2) {
+			assert_eq!(pair[0].manhattan_distance(&pair[1]), 1);
+		}
+	}
+
+	#[test]
+	pub fn test_astar_straight() {
+		let g = grid_from_rows(&[
+			".....",
+			".....",
+		]);
+		let path = astar(&g, Coord{x: 0, y: 0}, Coord{x: 4, y: 0}).unwrap();
+		assert_eq!(path, (0..5).map(|x| Coord{x: x, y: 0}).collect::<Vec<_>>());
+		assert_eq!(astar(&g, Coord{x: 2, y: 1}, Coord{x: 2, y: 1}), Some(vec![Coord{x: 2, y: 1}]));
+	}
+
+	#[test]
+	pub fn test_astar_around_wall() {
+		let g = grid_from_rows(&[
+			"..#..",
+			"..#..",
+			".....",
+		]);
+		let (start, goal) = (Coord{x: 0, y: 0}, Coord{x: 4, y: 0});
+		let path = astar(&g, start, goal).unwrap();
+		assert_walk(&path, start, goal);
+		assert_eq!(path.len(), 9);
+		assert!(path.iter().all(|c| c.x != 2 || c.y == 2));
+
+		// a single wall edge blocks just as well as an impasse tile
+		let mut g = grid_from_rows(&[
+			"...",
+		]);
+		g.add(&Coord{x: 1, y: 0}, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East));
+		assert_eq!(astar(&g, Coord{x: 0, y: 0}, Coord{x: 2, y: 0}), None);
+	}
+
+	#[test]
+	pub fn test_astar_unreachable() {
+		let g = grid_from_rows(&[
+			"..#..",
+			"..#..",
+			"..#..",
+		]);
+		assert_eq!(astar(&g, Coord{x: 0, y: 1}, Coord{x: 4, y: 1}), None);
+
+		// empty cells can't be walked on either
+		let g = grid_from_rows(&[
+			".. ..",
+		]);
+		assert_eq!(astar(&g, Coord{x: 0, y: 0}, Coord{x: 4, y: 0}), None);
+	}
+
+	#[test]
+	pub fn test_reachable() {
+		let g = grid_from_rows(&[
+			"..#...",
+			"..#...",
+			"..#. .",
+		]);
+		let left = reachable(&g, Coord{x: 0, y: 0});
+		let right = reachable(&g, Coord{x: 5, y: 2});
+		assert_eq!(left.len(), 6);
+		assert_eq!(right.len(), 8);
+		assert!(left.is_disjoint(&right));
+		assert!(left.iter().all(|c| c.x < 2));
+		assert!(right.contains(&Coord{x: 3, y: 2}));
+		assert!(!right.contains(&Coord{x: 4, y: 2}));
+
+		assert!(reachable(&g, Coord{x: 4, y: 2}).is_empty());
+		assert_eq!(reachable(&g, Coord{x: 2, y: 0}), HashSet::from([Coord{x: 2, y: 0}]));
+	}
+
+	#[test]
+	pub fn test_dijkstra_prefers_sidewalk() {
+		let mut map = TileMap::new(5, 2);
+		let road = [Coord{x: 1, y: 0}, Coord{x: 2, y: 0}, Coord{x: 3, y: 0}];
+		for x in 0..5 {
+			for y in 0..2 {
+				let c = Coord{x: x, y: y};
+				let ty = if road.contains(&c) { TileType::Road } else { TileType::Sidewalk };
+				map.set(Elevation(0), &c, Some(Tile::from_type(ty, PlaneDir::North)));
+			}
+		}
+
+		let (start, goal) = (Coord{x: 0, y: 0}, Coord{x: 4, y: 0});
+		let (path, cost) = dijkstra(&map, Elevation(0), start, goal, Tile::movement_cost).unwrap();
+		assert_walk(&path, start, goal);
+		assert_eq!(path.len(), 7);
+		assert!(path.iter().all(|c| !road.contains(c)));
+		assert_eq!(cost, 6 * (TileType::Sidewalk.movement_cost() + DirInfo::DEFAULT_COST));
+
+		// with a flat cost the straight line over the road wins
+		let (path, cost) = dijkstra(&map, Elevation(0), start, goal, |_| 1).unwrap();
+		assert_eq!(path.len(), 5);
+		assert_eq!(cost, 4 * 2);
+
+		// an impassable cost is never stepped on
+		let blocked = |t: &Tile| if t.ty == TileType::Road { u32::MAX } else { 1 };
+		let (path, _) = dijkstra(&map, Elevation(0), start, goal, blocked).unwrap();
+		assert!(path.iter().all(|c| !road.contains(c)));
+		assert!(dijkstra(&map, Elevation(1), start, goal, blocked).is_none());
+	}
+
+	#[test]
+	pub fn test_empty_gap_blocks() {
+		let mut map = TileMap::new(3, 1);
+		map.layers.insert(Elevation(0), grid_from_rows(&[". ."]));
+		let (a, gap, b) = (Coord{x: 0, y: 0}, Coord{x: 1, y: 0}, Coord{x: 2, y: 0});
+		assert!(!map.can_enter(Elevation(0), &gap, PlaneDir::West));
+		assert!(map.can_enter(Elevation(0), &b, PlaneDir::West));
+		assert!(!map.can_enter(Elevation(1), &b, PlaneDir::West));
+		assert!(!map.can_enter(Elevation(0), &Coord{x: 3, y: 0}, PlaneDir::West));
+
+		let grid = map.layer(Elevation(0)).unwrap();
+		assert_eq!(astar(grid, a, b), None);
+		assert_eq!(dijkstra(&map, Elevation(0), a, b, |_| 1), None);
+		assert_eq!(astar_3d(&map, (Elevation(0), a), (Elevation(0), b)), None);
+		assert!(flow_field(grid, b).is_empty());
+		assert_eq!(reachable(grid, a), HashSet::from([a]));
+
+		// filling the gap joins them
+		map.set(Elevation(0), &gap, Some(Tile::new()));
+		assert_eq!(astar(map.layer(Elevation(0)).unwrap(), a, b), Some(vec![a, gap, b]));
+	}
+
+	#[test]
+	pub fn test_dijkstra_edge_cost() {
+		let mut map = TileMap::new(3, 2);
+		map.layers.insert(Elevation(0), grid_from_rows(&[
+			"...",
+			"...",
+		]));
+		let (start, goal) = (Coord{x: 0, y: 0}, Coord{x: 2, y: 0});
+		let (path, cost) = dijkstra(&map, Elevation(0), start, goal, |_| 1).unwrap();
+		assert_eq!(path, vec![start, Coord{x: 1, y: 0}, goal]);
+		assert_eq!(cost, 4);
+
+		// a curb on one side of the edge is enough to make the long way cheaper
+		let mut curb = map.get(Elevation(0), &Coord{x: 1, y: 0}).cloned().unwrap();
+		curb.set(&PlaneDir::West, DirInfo{cost: 6, ..DirInfo::OPEN});
+		map.set(Elevation(0), &Coord{x: 1, y: 0}, Some(curb));
+		let (path, cost) = dijkstra(&map, Elevation(0), start, goal, |_| 1).unwrap();
+		assert_walk(&path, start, goal);
+		assert_eq!(path.len(), 5);
+		assert_ne!(path[1], Coord{x: 1, y: 0});
+		assert_eq!(cost, 4 * 2);
+
+		// coming the other way it costs the same and goes round too
+		let (path, cost) = dijkstra(&map, Elevation(0), Coord{x: 1, y: 0}, start, |_| 1).unwrap();
+		assert_eq!((path.len(), cost), (4, 3 * 2));
+		// tile and edge costs add up, so on dearer tiles going round costs
+		// more than the curb does: 2 + 6 straight over against 3 * (2 + 1)
+		let (path, cost) = dijkstra(&map, Elevation(0), start, Coord{x: 1, y: 0}, |_| 2).unwrap();
+		assert_eq!(path, vec![start, Coord{x: 1, y: 0}]);
+		assert_eq!(cost, 8);
+	}
+
+	#[test]
+	pub fn test_astar_3d_over_wall() {
+		// a wall splits the ground floor, the way across is a bridge upstairs
+		let mut map = TileMap::new(7, 1);
+		let ground = [
+			Tile::from_type(TileType::Freewalk, PlaneDir::North),
+			Tile::from_type(TileType::Stair, PlaneDir::East),
+			Tile::from_type(TileType::Impasse, PlaneDir::North),
+			Tile::from_type(TileType::Impasse, PlaneDir::North),
+			Tile::from_type(TileType::Impasse, PlaneDir::North),
+			Tile::from_type(TileType::Stair, PlaneDir::West),
+			Tile::from_type(TileType::Freewalk, PlaneDir::North),
+		];
+		for (x, t) in ground.into_iter().enumerate() {
+			map.set(Elevation(0), &Coord{x: x, y: 0}, Some(t));
+		}
+		for x in 2..5 {
+			map.set(Elevation(1), &Coord{x: x, y: 0}, Some(Tile::new()));
+		}
+
+		let start = (Elevation(0), Coord{x: 0, y: 0});
+		let goal = (Elevation(0), Coord{x: 6, y: 0});
+		let path = astar_3d(&map, start, goal).unwrap();
+		let expected = vec![(0, 0), (0, 1), (1, 2), (1, 3), (1, 4), (0, 5), (0, 6)];
+		assert_eq!(path, expected.into_iter().map(|(e, x)| (Elevation(e), Coord{x: x, y: 0})).collect::<Vec<_>>());
+
+		// and the route back the other way
+		assert_eq!(astar_3d(&map, goal, start).unwrap().len(), 7);
+
+		// without the bridge there's no way over
+		map.set(Elevation(1), &Coord{x: 3, y: 0}, None);
+		assert_eq!(astar_3d(&map, start, goal), None);
+	}
+
+	#[test]
+	pub fn test_astar_batch() {
+		let mut map = TileMap::new(8, 6);
+		*map.layer_mut(Elevation(0)) = crate::mapgen::generate_maze(8, 6, 11);
+		map.set(Elevation(1), &Coord{x: 0, y: 0}, Some(Tile::new()));
+		let corridors: Vec<Coord> = map.layer(Elevation(0)).unwrap().iter()
+			.filter(|(_, t)| !t.blocks())
+			.map(|(c, _)| c)
+			.collect();
+
+		let mut requests = Vec::new();
+		for a in &corridors {
+			for b in corridors.iter().step_by(2) {
+				requests.push(((Elevation(0), *a), (Elevation(0), *b)));
+			}
+		}
+		// one with no route and one off to a layer that can't be reached
+		requests.push(((Elevation(0), corridors[0]), (Elevation(0), Coord{x: 0, y: 0})));
+		requests.push(((Elevation(0), corridors[0]), (Elevation(1), Coord{x: 0, y: 0})));
+
+		let serial: Vec<_> = requests.iter().map(|(a, b)| astar_3d(&map, *a, *b)).collect();
+		let batch = astar_batch(&map, &requests);
+		assert_eq!(batch, serial);
+		assert!(batch[..batch.len() - 2].iter().all(|r| r.is_some()));
+		assert_eq!(batch[batch.len() - 2..], [None, None]);
+	}
+
+	#[test]
+	pub fn test_wheelchair_route() {
+		let mut map = TileMap::new(5, 2);
+		for x in 0..3 {
+			map.set(Elevation(0), &Coord{x: x, y: 1}, Some(Tile::new()));
+		}
+		map.set(Elevation(0), &Coord{x: 0, y: 0}, Some(Tile::new()));
+		map.set(Elevation(0), &Coord{x: 1, y: 0}, Some(Tile::from_type(TileType::Stair, PlaneDir::East)));
+		map.set(Elevation(0), &Coord{x: 3, y: 1}, Some(Tile::from_type(TileType::Ramp, PlaneDir::East)));
+		for c in [(2, 0), (3, 0), (4, 0), (4, 1)] {
+			map.set(Elevation(1), &Coord::from(&c), Some(Tile::new()));
+		}
+
+		let start = (Elevation(0), Coord{x: 0, y: 0});
+		let goal = (Elevation(1), Coord{x: 2, y: 0});
+		let direct = astar_3d_with(&map, start, goal, false).unwrap();
+		assert_eq!(direct.len(), 3);
+		assert_eq!(direct[1], (Elevation(0), Coord{x: 1, y: 0}));
+
+		let accessible = astar_3d_with(&map, start, goal, true).unwrap();
+		assert_eq!(accessible.len(), 9);
+		assert!(accessible.contains(&(Elevation(0), Coord{x: 3, y: 1})));
+		assert!(!accessible.contains(&(Elevation(0), Coord{x: 1, y: 0})));
+
+		// no ramp, no accessible route
+		map.set(Elevation(0), &Coord{x: 3, y: 1}, None);
+		assert_eq!(astar_3d_with(&map, start, goal, true), None);
+		assert_eq!(astar_3d(&map, start, goal), Some(direct));
+	}
+
+	#[test]
+	pub fn test_line_of_sight() {
+		let g = grid_from_rows(&[
+			".....",
+			".....",
+			".....",
+		]);
+		assert!(line_of_sight(&g, Coord{x: 0, y: 0}, Coord{x: 4, y: 2}));
+		assert!(line_of_sight(&g, Coord{x: 4, y: 1}, Coord{x: 0, y: 1}));
+
+		let g = grid_from_rows(&[
+			".....",
+			"..#..",
+			".....",
+		]);
+		assert!(!line_of_sight(&g, Coord{x: 0, y: 1}, Coord{x: 4, y: 1}));
+		assert!(line_of_sight(&g, Coord{x: 0, y: 0}, Coord{x: 4, y: 0}));
+		// a wall at the far end can still be seen
+		assert!(line_of_sight(&g, Coord{x: 0, y: 1}, Coord{x: 2, y: 1}));
+		// missing tiles block too
+		let g = grid_from_rows(&[
+			".. ..",
+		]);
+		assert!(!line_of_sight(&g, Coord{x: 0, y: 0}, Coord{x: 4, y: 0}));
+	}
+
+	#[test]
+	pub fn test_line_of_sight_corner() {
+		// the diagonal from (0, 0) to (2, 2) grazes the corner of the wall at (1, 0)
+		let g = grid_from_rows(&[
+			".#.",
+			"...",
+			"...",
+		]);
+		assert_eq!(Coord{x: 0, y: 0}.line_to(&Coord{x: 2, y: 2}).len(), 3);
+		assert!(line_of_sight(&g, Coord{x: 0, y: 0}, Coord{x: 2, y: 2}));
+
+		// walls on both sides of the corner close the gap
+		let g = grid_from_rows(&[
+			".#.",
+			"#..",
+			"...",
+		]);
+		assert!(!line_of_sight(&g, Coord{x: 0, y: 0}, Coord{x: 2, y: 2}));
+	}
+
+	#[test]
+	pub fn test_walkability_grid() {
+		let mut map = TileMap::new(6, 4);
+		*map.layer_mut(Elevation(0)) = crate::mapgen::generate_random(6, 4, 3);
+		map.set(Elevation(0), &Coord{x: 1, y: 1}, Some(Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::East)));
+		map.set(Elevation(0), &Coord{x: 4, y: 2}, Some(Tile::from_template(TileDirTemplate::Stair, PlaneDir::South)));
+		map.set(Elevation(0), &Coord{x: 5, y: 3}, None);
+
+		let check = |walk: &WalkabilityGrid, map: &TileMap| {
+			let grid = map.layer(Elevation(0)).unwrap();
+			let mut steps = Vec::new();
+			for (c, slot) in grid.cells() {
+				for d in PlaneDir::ALL {
+					let expected = slot.as_ref().is_some_and(|t| t.is_enterable_from(&d));
+					assert_eq!(walk.is_enterable_from(&c, d), expected, "{:?} {:?}", c, d);
+				}
+				walk.steps_into(&c, &mut steps);
+				assert_eq!(steps, grid_steps(grid, &c));
+			}
+		};
+		let mut walk = WalkabilityGrid::from_map(&map, Elevation(0));
+		assert!(walk.is_current(&map));
+		check(&walk, &map);
+
+		map.set(Elevation(0), &Coord{x: 2, y: 2}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		assert!(!walk.is_current(&map));
+		walk.refresh(&map);
+		assert!(walk.is_current(&map));
+		check(&walk, &map);
+
+		assert!(!WalkabilityGrid::from_map(&map, Elevation(5)).is_enterable_from(&Coord{x: 0, y: 0}, PlaneDir::North));
+	}
+
+	#[test]
+	pub fn test_connected_components() {
+		let door = grid_from_rows(&[
+			"..#..",
+			"..#..",
+			".....",
+		]);
+		let ids = connected_components(&door);
+		assert_eq!(ids.len(), 13);
+		assert!(ids.values().all(|id| *id == 0));
+
+		let walled = grid_from_rows(&[
+			"..#..",
+			"..#..",
+			"..#. ",
+		]);
+		let ids = connected_components(&walled);
+		assert_eq!(ids.len(), 11);
+		assert_eq!(ids[&Coord{x: 0, y: 0}], 0);
+		assert_eq!(ids[&Coord{x: 3, y: 0}], 1);
+		assert_eq!(ids[&Coord{x: 1, y: 2}], ids[&Coord{x: 0, y: 0}]);
+		assert_eq!(ids[&Coord{x: 3, y: 2}], ids[&Coord{x: 4, y: 1}]);
+		assert!(!ids.contains_key(&Coord{x: 2, y: 1}));
+		assert!(!ids.contains_key(&Coord{x: 4, y: 2}));
+	}
+
+	#[test]
+	pub fn test_path_directions() {
+		let path: Vec<Coord> = [(0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (1, 2)].iter().map(Coord::from).collect();
+		assert_eq!(path_directions(&path), vec![
+			PlaneDir::East,
+			PlaneDir::East,
+			PlaneDir::South,
+			PlaneDir::South,
+			PlaneDir::West,
+		]);
+
+		// the jump from (1, 2) to (3, 0) is dropped
+		let jumpy: Vec<Coord> = [(1, 2), (3, 0), (3, 1)].iter().map(Coord::from).collect();
+		assert_eq!(path_directions(&jumpy), vec![PlaneDir::South]);
+		assert!(path_directions(&path[..1]).is_empty());
+	}
+
+	#[test]
+	pub fn test_cached_flow_field() {
+		let mut map = TileMap::new(4, 2);
+		*map.layer_mut(Elevation(0)) = grid_from_rows(&[
+			"....",
+			".#..",
+		]);
+		let mut cached = CachedFlowField::new(Elevation(0));
+		let goal = Coord{x: 0, y: 0};
+		let expected = flow_field(map.layer(Elevation(0)).unwrap(), goal);
+
+		for _ in 0..5 {
+			assert_eq!(cached.get(&map, goal), &expected);
+		}
+		assert_eq!(cached.computations, 1);
+
+		cached.get(&map, Coord{x: 3, y: 1});
+		assert_eq!(cached.computations, 2);
+
+		map.set(Elevation(0), &Coord{x: 2, y: 0}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		let edited = flow_field(map.layer(Elevation(0)).unwrap(), Coord{x: 3, y: 1});
+		assert_eq!(cached.get(&map, Coord{x: 3, y: 1}), &edited);
+		assert_eq!(cached.computations, 3);
+		cached.get(&map, Coord{x: 3, y: 1});
+		assert_eq!(cached.computations, 3);
+
+		let mut missing = CachedFlowField::new(Elevation(4));
+		assert!(missing.get(&map, goal).is_empty());
+	}
+
+	#[test]
+	pub fn test_flow_field() {
+		let g = grid_from_rows(&[
+			"#####.",
+			"......",
+			"##### ",
+			"..#...",
+		]);
+		let goal = Coord{x: 0, y: 1};
+		let field = flow_field(&g, goal);
+
+		// along the corridor everything heads west to the goal
+		for x in 1..6 {
+			assert_eq!(*field.get(&Coord{x: x, y: 1}), Some(PlaneDir::West));
+		}
+		assert_eq!(*field.get(&Coord{x: 5, y: 0}), Some(PlaneDir::South));
+		assert_eq!(*field.get(&goal), None);
+
+		// walls, holes and the closed off room below point nowhere
+		assert_eq!(*field.get(&Coord{x: 0, y: 0}), None);
+		assert_eq!(*field.get(&Coord{x: 5, y: 2}), None);
+		assert_eq!(*field.get(&Coord{x: 0, y: 3}), None);
+		assert_eq!(*field.get(&Coord{x: 4, y: 3}), None);
+
+		// following the field from anywhere reachable ends at the goal
+		let mut c = Coord{x: 5, y: 0};
+		while let Some(dir) = field.get(&c) {
+			c = c.step(*dir).unwrap();
+		}
+		assert_eq!(c, goal);
+	}
+
+	#[test]
+	pub fn test_open_sets_agree() {
+		let grids = [
+			crate::mapgen::generate_maze(15, 11, 4),
+			crate::mapgen::generate_random(14, 10, 9),
+			grid_from_rows(&[
+				"......",
+				".####.",
+				"......",
+			]),
+		];
+		for g in &grids {
+			let tiles: Vec<Coord> = g.iter().filter(|(_, t)| !t.blocks()).map(|(c, _)| c).collect();
+			for start in tiles.iter().step_by(5) {
+				for goal in tiles.iter().step_by(2) {
+					let (heap, heap_stats) = astar_with(g, *start, *goal, HeapOpenSet::new());
+					let (bucket, bucket_stats) = astar_with(g, *start, *goal, BucketOpenSet::new());
+					assert_eq!(heap, bucket, "{:?} to {:?}", start, goal);
+					assert_eq!(heap_stats, bucket_stats);
+					assert!(heap_stats.expanded <= tiles.len(), "{:?} to {:?} expanded {}", start, goal, heap_stats.expanded);
+					assert_eq!(heap, astar(g, *start, *goal));
+				}
+			}
+		}
+
+		let (path, stats) = astar_with(&grids[2], Coord{x: 0, y: 1}, Coord{x: 5, y: 1}, BucketOpenSet::new());
+		assert_eq!(path.map(|p| p.len()), Some(8));
+		assert!(stats.expanded >= 7 && stats.pushed > stats.expanded);
+	}
+
+	#[test]
+	pub fn test_stale_entries_skipped() {
+		// 1 is pushed at 10 straight from 0 and again at 2 by way of 2, the
+		// entry at 10 comes off before the goal does and has to be ignored
+		let edges = |n: &u32| match *n {
+			0 => vec![(1, 10), (2, 1)],
+			2 => vec![(1, 1)],
+			1 => vec![(3, 20)],
+			_ => vec![],
+		};
+		for heap in [true, false] {
+			let mut stats = SearchStats::default();
+			let found = if heap {
+				search_with(HeapOpenSet::new(), &mut stats, 0, |n| *n == 3, edges, |_| 0)
+			} else {
+				search_with(BucketOpenSet::new(), &mut stats, 0, |n| *n == 3, edges, |_| 0)
+			};
+			assert_eq!(found, Some((vec![0, 2, 1, 3], 22)));
+			assert_eq!(stats, SearchStats{expanded: 3, pushed: 5});
+		}
+	}
+
+	#[test]
+	pub fn test_path_cache() {
+		let mut map = TileMap::new(5, 3);
+		*map.layer_mut(Elevation(0)) = grid_from_rows(&[
+			"..#..",
+			"..#..",
+			".....",
+		]);
+		let mut cache = PathCache::new();
+		let (start, goal) = (Coord{x: 0, y: 0}, Coord{x: 4, y: 0});
+
+		let first = cache.route(&map, Elevation(0), start, goal).cloned();
+		assert_eq!(first.as_ref().map(|p| p.len()), Some(9));
+		assert_eq!((cache.hits, cache.misses), (0, 1));
+		assert_eq!(cache.route(&map, Elevation(0), start, goal).cloned(), first);
+		assert_eq!((cache.hits, cache.misses), (1, 1));
+
+		// other endpoints are their own entry
+		cache.route(&map, Elevation(0), goal, start);
+		assert_eq!((cache.hits, cache.misses), (1, 2));
+
+		// an edit throws away what was cached
+		map.set(Elevation(0), &Coord{x: 2, y: 2}, Some(Tile::from_template(TileDirTemplate::Impasse, PlaneDir::North)));
+		assert_eq!(cache.route(&map, Elevation(0), start, goal), None);
+		assert_eq!((cache.hits, cache.misses), (1, 3));
+
+		let cache = PathCache::with_capacity(50);
+		assert!(cache.routes.capacity() >= 50);
+		assert_eq!((cache.hits, cache.misses), (0, 0));
+	}
+
+	#[test]
+	pub fn test_caches_across_maps() {
+		let layer = |rows: &[&str]| {
+			let mut map = TileMap::new(3, 1);
+			*map.layer_mut(Elevation(0)) = grid_from_rows(rows);
+			map
+		};
+		let (open, walled) = (layer(&["..."]), layer(&[".#."]));
+		// both have had the one edit, so only the id tells them apart
+		assert_eq!(open.version(), walled.version());
+		let (start, goal) = (Coord{x: 0, y: 0}, Coord{x: 2, y: 0});
+
+		let mut cache = PathCache::new();
+		assert!(cache.route(&open, Elevation(0), start, goal).is_some());
+		assert_eq!(cache.route(&walled, Elevation(0), start, goal), None);
+		assert_eq!((cache.hits, cache.misses), (0, 2));
+		// a copy is a different map too, even before it's edited
+		let copy = open.clone();
+		assert!(cache.route(&copy, Elevation(0), start, goal).is_some());
+		assert_eq!((cache.hits, cache.misses), (0, 3));
+
+		let mut field = CachedFlowField::new(Elevation(0));
+		assert_eq!(field.get(&open, goal).get(&start), &Some(PlaneDir::East));
+		assert_eq!(field.get(&walled, goal).get(&start), &None);
+		assert_eq!(field.computations, 2);
+
+		let walk = WalkabilityGrid::from_map(&open, Elevation(0));
+		assert!(walk.is_current(&open));
+		assert!(!walk.is_current(&walled));
+
+		// and so is the same map read back from a file, which starts over at version 0
+		#[cfg(feature = "serde")]
+		{
+			let mut reloaded = TileMap::from_ron(&walled.to_ron().unwrap()).unwrap();
+			reloaded.version = open.version();
+			assert_eq!(cache.route(&reloaded, Elevation(0), start, goal), None);
+		}
+	}
+
+	fn assert_same_as_astar(g: &Grid<Tile>) {
+		let tiles: Vec<Coord> = (0..g.height)
+			.flat_map(|y| (0..g.width).map(move |x| Coord{x: x, y: y}))
+			.filter(|c| g.get(c).is_some())
+			.collect();
+		for start in tiles.iter().step_by(3) {
+			for goal in &tiles {
+				let slow = astar(g, *start, *goal);
+				let fast = astar_fast(g, *start, *goal);
+				assert_eq!(slow.as_ref().map(|p| p.len()), fast.as_ref().map(|p| p.len()), "{:?} to {:?}", start, goal);
+				if let Some(path) = fast {
+					assert_walk(&path, *start, *goal);
+				}
+			}
+		}
+	}
+
+	#[test]
+	pub fn test_astar_fast_open() {
+		let g = grid_from_rows(&[
+			"........",
+			"........",
+			"........",
+			"........",
+			"........",
+		]);
+		assert_same_as_astar(&g);
+		let path = astar_fast(&g, Coord{x: 0, y: 0}, Coord{x: 7, y: 4}).unwrap();
+		assert_eq!(path.len(), 12);
+	}
+
+	#[test]
+	pub fn test_astar_fast_walled() {
+		let mut g = grid_from_rows(&[
+			"....#.....",
+			".##.#.###.",
+			".#..#...#.",
+			".#.###.#..",
+			"......... ",
+			"#####.###.",
+			"..........",
+		]);
+		assert_same_as_astar(&g);
+
+		// part walls and stairs make it fall back to single steps
+		g.add(&Coord{x: 2, y: 4}, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::West));
+		g.add(&Coord{x: 6, y: 6}, Tile::from_template(TileDirTemplate::Stair, PlaneDir::North));
+		g.add(&Coord{x: 3, y: 0}, Tile::from_template(TileDirTemplate::SingleWall, PlaneDir::South));
+		assert_same_as_astar(&g);
+	}
+
+	#[test]
+	pub fn test_distance_to_impasse() {
+		let g = grid_from_rows(&[
+			"#######",
+			"#.....#",
+			"#.....#",
+			"#.....#",
+			"#######",
+		]);
+		let dist = distance_to_impasse(&g);
+		let row = |y| (0..7).map(|x| dist.get(&Coord{x: x, y: y}).unwrap()).collect::<Vec<_>>();
+		assert_eq!(row(0), vec![0; 7]);
+		assert_eq!(row(1), vec![0, 1, 1, 1, 1, 1, 0]);
+		assert_eq!(row(2), vec![0, 1, 2, 2, 2, 1, 0]);
+
+		// an empty cell counts as an obstacle too
+		let g = grid_from_rows(&[
+			"... ",
+		]);
+		let dist = distance_to_impasse(&g);
+		assert_eq!(*dist.get(&Coord{x: 0, y: 0}), Some(3));
+		assert_eq!(*dist.get(&Coord{x: 3, y: 0}), Some(0));
+
+		let open = distance_to_impasse(&grid_from_rows(&["..", ".."]));
+		assert_eq!(*open.get(&Coord{x: 1, y: 1}), None);
+	}
+}
@@ -0,0 +1,13 @@
+//! the types most code over a map needs, all at once
+//!
+//! ```
+//! use poubelle::prelude::*;
+//!
+//! let mut grid: Grid<Tile> = Grid::new(3, 2);
+//! grid.add(&Coord{x: 1, y: 0}, Tile::from_type(TileType::Road, PlaneDir::North));
+//! assert_eq!(grid.count_occupied(), 1);
+//! assert_eq!(grid.get(&Coord{x: 1, y: 0}).as_ref().map(|t| t.ty()), Some(TileType::Road));
+//! ```
+
+pub use crate::path::{MapPos, OpenSet};
+pub use crate::{Coord, DirInfo, Elevation, ElevationDelta, Grid, LayerMeta, PlaneDir, Tile, TileDirTemplate, TileMap, TileType};